@@ -1,43 +1,102 @@
 use std::{
-    collections::HashMap, env, fs, io, os::unix::process::CommandExt, path::Path, process::Command,
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, BufRead, Write},
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{self, Command},
     sync::Arc,
+    thread::{self, JoinHandle},
+    time::SystemTime,
 };
 
 use glyphon::TextArea;
 use log::{error, info};
 use winit::{
     application::ApplicationHandler,
-    dpi::{LogicalPosition, LogicalSize},
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, Position},
     event::WindowEvent,
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     keyboard::NamedKey,
     window::Window,
 };
 
+use crate::daemon;
+use crate::desktop_entry;
 use crate::executables::{self, Executable};
+use crate::history::History;
+use crate::icon_renderer::{Icon, IconRenderer};
+use crate::icons::IconLoader;
+use crate::matcher::{CaseMatching, Matcher};
+use crate::rect_renderer::{Rect, RectRenderer};
 use crate::{config::Config, executables::CACHE_FILE_NAME};
 
+/// Scales the logarithm of an executable's frecency before it is added to its match score
+const FRECENCY_WEIGHT: f64 = 4.0;
+/// Space between an icon and the edges of its row
+const ICON_PADDING: f32 = 3.0;
+/// Matches on keywords, generic names and comments count for less than matches on the name
+const SECONDARY_SCORE_DIVISOR: i32 = 2;
+
+/// What happens with the entry the user picks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Entries are the scanned executables, the picked one gets launched
+    Launcher,
+    /// Entries are read from stdin, the picked one gets printed to stdout like dmenu does
+    Dmenu,
+}
+
 struct AppState {
+    mode: Mode,
     search_entry: String,
     config: Config,
+    history: History,
     paths: Vec<String>,
     executables: Vec<Executable>,
     matching_executable_indexes: Vec<usize>,
     selected_index: usize,
+    /// Which copy of the selected binary gets launched, Tab cycles through the ones in PATH
+    launch_target: usize,
+    /// The selected entry has to be confirmed, picking it again runs it
+    confirming: bool,
     ctrl_pressed: bool,
+    shift_pressed: bool,
+    exit_code: i32,
+    /// Hide the window instead of exiting, to show it again when a client asks
+    daemon: bool,
+    /// Whether the window is shown, it is hidden while the daemon waits
+    shown: bool,
 }
 
 impl AppState {
-    fn new(config: Config, paths: Vec<String>, executables: Vec<Executable>) -> Self {
-        Self {
+    fn new(
+        mode: Mode,
+        config: Config,
+        history: History,
+        paths: Vec<String>,
+        executables: Vec<Executable>,
+        daemon: bool,
+    ) -> Self {
+        let mut state = Self {
+            mode,
             search_entry: String::with_capacity(255),
             config,
+            history,
             paths,
             executables,
             matching_executable_indexes: Vec::with_capacity(8),
             selected_index: 0,
+            launch_target: 0,
+            confirming: false,
             ctrl_pressed: false,
-        }
+            shift_pressed: false,
+            exit_code: 0,
+            daemon,
+            shown: !daemon,
+        };
+        state.update_matching_executable_indexes();
+        state
     }
 
     fn append_to_search(&mut self, s: &str) {
@@ -52,32 +111,94 @@ impl AppState {
 
     fn update_matching_executable_indexes(&mut self) {
         self.selected_index = 0;
+        self.launch_target = 0;
+        self.confirming = false;
         self.matching_executable_indexes.clear();
 
         if self.search_entry.is_empty() {
+            match self.mode {
+                // dmenu lists every entry until something is typed
+                Mode::Dmenu => self
+                    .matching_executable_indexes
+                    .extend(0..self.executables.len()),
+                // The launcher lists what was launched before, most frecent first
+                Mode::Launcher => {
+                    let mut launched_indexes: Vec<(usize, f64)> = self
+                        .executables
+                        .iter()
+                        .enumerate()
+                        .map(|(i, executable)| {
+                            (i, self.history.get_frecency(&executable.get_history_key()))
+                        })
+                        .filter(|(_, frecency)| *frecency > 0.0)
+                        .collect();
+                    launched_indexes.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+                    self.matching_executable_indexes
+                        .extend(launched_indexes.into_iter().map(|(i, _)| i));
+                }
+            }
             return;
         }
 
-        for i in 0..self.executables.len() {
-            let display_name = &self.executables[i].get_display_text();
-            if *display_name == self.search_entry {
-                self.matching_executable_indexes.insert(0, i);
-            } else if display_name.contains(&self.search_entry) {
-                self.matching_executable_indexes.push(i);
-            }
-        }
+        // dmenu matches case-sensitively unless it is told otherwise
+        let case_matching = if self.config.case_insensitive {
+            CaseMatching::Insensitive
+        } else if self.mode == Mode::Dmenu {
+            CaseMatching::Sensitive
+        } else {
+            CaseMatching::Smart
+        };
+        let matcher = Matcher::new(&self.search_entry, case_matching);
+        let mut scored_indexes: Vec<(usize, bool, i32)> = self
+            .executables
+            .iter()
+            .enumerate()
+            .filter_map(|(i, executable)| {
+                let display_text = executable.get_display_text();
+                // Keywords and descriptions are long and loosely related, so fuzzy matches on
+                // them would mostly be noise
+                let secondary_score = executable
+                    .get_secondary_search_texts()
+                    .filter(|text| matcher.is_substring(text))
+                    .filter_map(|text| matcher.score(text))
+                    .max()
+                    .map(|score| score / SECONDARY_SCORE_DIVISOR);
+                let score = matcher.score(display_text).max(secondary_score);
+                score.map(|score| {
+                    // Frequently and recently launched executables rank higher
+                    let frecency = self.history.get_frecency(&executable.get_history_key());
+                    let boost = (frecency.ln_1p() * FRECENCY_WEIGHT) as i32;
+                    (i, matcher.is_exact(display_text), score + boost)
+                })
+            })
+            .collect();
+        // Exact matches first, then by score. Shorter texts win ties since less of them is left
+        // unmatched, and the sort is stable so the original order decides the rest.
+        scored_indexes.sort_by(|(a, a_exact, a_score), (b, b_exact, b_score)| {
+            b_exact
+                .cmp(a_exact)
+                .then(b_score.cmp(a_score))
+                .then_with(|| {
+                    self.executables[*a]
+                        .get_display_text()
+                        .len()
+                        .cmp(&self.executables[*b].get_display_text().len())
+                })
+        });
+        self.matching_executable_indexes
+            .extend(scored_indexes.into_iter().map(|(i, _, _)| i));
 
         info!("The executables are:");
-        let mut c = 0;
-        for i in &self.matching_executable_indexes {
+        for (c, i) in self.matching_executable_indexes.iter().enumerate() {
             info!("{}: {}", c, self.executables[*i].get_display_text());
-            c += 1;
         }
     }
 
     fn increment_selected_index(&mut self) {
         self.selected_index =
-            (self.selected_index + 1).min(self.matching_executable_indexes.len() - 1);
+            (self.selected_index + 1).min(self.matching_executable_indexes.len().saturating_sub(1));
+        self.launch_target = 0;
+        self.confirming = false;
         info!("Selected index: {}", self.selected_index);
     }
 
@@ -85,14 +206,50 @@ impl AppState {
         if self.selected_index > 0 {
             self.selected_index -= 1;
         }
+        self.launch_target = 0;
+        self.confirming = false;
         info!("selected index: {}", self.selected_index);
     }
 
-    fn get_selected_executable(&self) -> Option<&Executable> {
-        if self.selected_index >= self.matching_executable_indexes.len() {
-            return None;
+    /// Replaces the executables with the ones of a newer scan, keeping the search entry and the
+    /// selected executable
+    fn set_executables(&mut self, executables: Vec<Executable>) {
+        let selected_key = self
+            .get_executable(self.selected_index)
+            .map(|executable| executable.get_history_key());
+        self.executables = executables;
+        self.update_matching_executable_indexes();
+
+        if let Some(selected_key) = selected_key {
+            if let Some(index) = self
+                .matching_executable_indexes
+                .iter()
+                .position(|i| self.executables[*i].get_history_key() == selected_key)
+            {
+                self.selected_index = index;
+            }
+        }
+    }
+
+    /// Picks the next copy of the selected binary to launch
+    fn cycle_launch_target(&mut self) {
+        let target_count = self
+            .get_executable(self.selected_index)
+            .map(|executable| executable.get_launch_targets().count())
+            .unwrap_or(0);
+        if target_count > 1 {
+            self.launch_target = (self.launch_target + 1) % target_count;
+        }
+    }
+
+    /// Which copy of the matching executable at `index` gets launched, only the selected one can
+    /// launch another than the first
+    fn get_launch_target(&self, index: usize) -> usize {
+        if index == self.selected_index {
+            self.launch_target
+        } else {
+            0
         }
-        Some(&self.executables[self.matching_executable_indexes[self.selected_index]])
     }
 
     fn get_executable(&self, index: usize) -> Option<&Executable> {
@@ -102,13 +259,183 @@ impl AppState {
         None
     }
 
+    /// Picks the matching executable at `index`, unless it has to be confirmed first. Then it
+    /// gets selected and picking it again confirms it. Returns whether it was picked.
+    fn pick_or_confirm(&mut self, index: usize) -> bool {
+        let needs_confirmation = self
+            .get_executable(index)
+            .and_then(|executable| executable.custom.as_ref())
+            .is_some_and(|custom| custom.confirm);
+        if needs_confirmation && !(self.confirming && index == self.selected_index) {
+            self.selected_index = index;
+            self.launch_target = 0;
+            self.confirming = true;
+            return false;
+        }
+        self.confirming = false;
+        self.pick(index);
+        true
+    }
+
+    /// Launches the matching executable at `index`, or prints it to stdout in dmenu mode. With
+    /// no executable at `index`, dmenu mode prints the typed text instead and launcher mode does
+    /// nothing.
+    fn pick(&mut self, index: usize) {
+        let executable = self.get_executable(index).cloned();
+        match self.mode {
+            Mode::Launcher => {
+                if let Some(executable) = executable {
+                    self.history.record_launch(&executable.get_history_key());
+                    if let Some(custom) = &executable.custom {
+                        let args = [custom.command.clone()]
+                            .into_iter()
+                            .chain(custom.args.iter().cloned())
+                            .collect();
+                        run_command(args, None, custom.cwd.as_deref(), custom.env.clone());
+                        return;
+                    }
+
+                    // Desktop files run their Exec, unless one of their binary's paths is picked
+                    let target = self.get_launch_target(index);
+                    let binary_path = if executable.is_desktop_file() {
+                        target
+                            .checked_sub(1)
+                            .and_then(|target| executable.get_binary_paths().nth(target))
+                    } else {
+                        executable.get_binary_paths().nth(target)
+                    };
+                    let args = match binary_path {
+                        Some(path) => vec![path.to_string()],
+                        None if executable.is_desktop_file() => {
+                            match desktop_entry::split_exec_args(&executable.command) {
+                                Ok(args) => args.into_iter().map(|arg| arg.value).collect(),
+                                Err(_) => {
+                                    error!("Malformed command '{}'", executable.command);
+                                    return;
+                                }
+                            }
+                        }
+                        None => {
+                            error!("Unknown path for '{}'", executable.command);
+                            return;
+                        }
+                    };
+
+                    let needs_terminal = executable.terminal
+                        || binary_path
+                            .and_then(|path| Path::new(path).file_name())
+                            .is_some_and(|binary_name| {
+                                self.config
+                                    .tui_binaries
+                                    .iter()
+                                    .any(|tui_binary| binary_name == tui_binary.as_str())
+                            });
+                    let terminal_args = if needs_terminal {
+                        let Some(terminal_args) = get_terminal_args(&self.config) else {
+                            error!(
+                                "No terminal emulator found to run '{}' in, set `terminal` in the config",
+                                executable.get_display_text()
+                            );
+                            return;
+                        };
+                        Some(terminal_args)
+                    } else {
+                        None
+                    };
+
+                    let envs = if executable.prefers_non_default_gpu {
+                        self.config.non_default_gpu_env.clone()
+                    } else {
+                        HashMap::new()
+                    };
+                    run_command(args, terminal_args, executable.working_dir.as_deref(), envs);
+                }
+            }
+            Mode::Dmenu => {
+                let output = executable
+                    .as_ref()
+                    .map(|executable| executable.get_display_text())
+                    .unwrap_or(&self.search_entry);
+                print_selection(output);
+            }
+        }
+    }
+
+    /// Closes the app without picking anything. dmenu signals this with exit code 1.
+    fn cancel(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, window: &Window) {
+        if self.mode == Mode::Dmenu {
+            self.exit_code = 1;
+        }
+        self.close(event_loop, window);
+    }
+
+    /// Exits, or hides the window when running as a daemon
+    fn close(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, window: &Window) {
+        if !self.daemon {
+            event_loop.exit();
+            return;
+        }
+        window.set_visible(false);
+        self.shown = false;
+        // The key release happens while the window is hidden
+        self.ctrl_pressed = false;
+        self.shift_pressed = false;
+    }
+
+    /// Shows the hidden window of the daemon with an empty search entry
+    fn show(&mut self, window: &Window) {
+        self.search_entry.clear();
+        self.update_matching_executable_indexes();
+        self.shown = true;
+        window.set_visible(true);
+        window.focus_window();
+        window.request_redraw();
+    }
+
+    /// Number of executables that fit in the window below the search entry
+    fn get_visible_rows(&self, height: f32) -> usize {
+        let fitting_rows = ((height / self.config.line_height) as usize)
+            .saturating_sub(1)
+            .max(1);
+        match self.config.lines {
+            Some(lines) => fitting_rows.min(lines.max(1)),
+            None => fitting_rows,
+        }
+    }
+
+    /// Index of the first matching executable that fits in the window, so the selection stays
+    /// visible when the list is longer than the window
+    fn get_first_visible_index(&self, height: f32) -> usize {
+        (self.selected_index + 1).saturating_sub(self.get_visible_rows(height))
+    }
+
+    /// The matching executables that fit in the window, from top to bottom
+    fn get_visible_executables(&self, height: f32) -> impl Iterator<Item = &Executable> {
+        let first_visible_index = self.get_first_visible_index(height);
+        let last_visible_index = self
+            .matching_executable_indexes
+            .len()
+            .min(first_visible_index + self.get_visible_rows(height));
+        self.matching_executable_indexes[first_visible_index..last_visible_index]
+            .iter()
+            .map(|index| &self.executables[*index])
+    }
+
+    /// Only launched entries have icons, dmenu entries are just text
+    fn shows_icons(&self) -> bool {
+        self.mode == Mode::Launcher && self.config.show_icons
+    }
+
     fn get_text_buffers(
         &self,
         font_system: &mut glyphon::FontSystem,
         width: f32,
         height: f32,
     ) -> Vec<glyphon::Buffer> {
-        let font = glyphon::Family::Monospace;
+        let font = match &self.config.font_family {
+            Some(font_family) => glyphon::Family::Name(font_family),
+            None => glyphon::Family::Monospace,
+        };
         let font_size = self.config.font_size;
         let line_height = self.config.line_height;
 
@@ -118,36 +445,89 @@ impl AppState {
             glyphon::Buffer::new(font_system, glyphon::Metrics::new(font_size, line_height));
 
         search_entry_text_buffer.set_size(font_system, Some(width), Some(height));
-        search_entry_text_buffer.set_text(
-            font_system,
-            &self.search_entry,
-            glyphon::Attrs::new().family(font),
-            glyphon::Shaping::Advanced,
-        );
+        let attrs = glyphon::Attrs::new().family(font);
+        let confirming = self
+            .get_executable(self.selected_index)
+            .filter(|_| self.confirming);
+        if let Some(executable) = confirming {
+            search_entry_text_buffer.set_text(
+                font_system,
+                &format!(
+                    "Run '{}'? Enter to confirm, Escape to go back",
+                    executable.get_display_text()
+                ),
+                attrs.color(self.config.font_color_highlighted),
+                glyphon::Shaping::Advanced,
+            );
+        } else {
+            match &self.config.prompt {
+                Some(prompt) => search_entry_text_buffer.set_rich_text(
+                    font_system,
+                    [
+                        (
+                            prompt.as_str(),
+                            attrs.color(self.config.font_color_highlighted),
+                        ),
+                        (" ", attrs),
+                        (self.search_entry.as_str(), attrs),
+                    ],
+                    attrs,
+                    glyphon::Shaping::Advanced,
+                ),
+                None => search_entry_text_buffer.set_text(
+                    font_system,
+                    &self.search_entry,
+                    attrs,
+                    glyphon::Shaping::Advanced,
+                ),
+            };
+        }
         search_entry_text_buffer.shape_until_scroll(font_system, false);
         text_buffers.push(search_entry_text_buffer);
 
         fn get_index_hint(i: usize) -> String {
             if i <= 8 {
-                return format!("(Ctrl+{})", i + 1);
+                format!("(Ctrl+{})", i + 1)
             } else if i == 9 {
-                return format!("(Ctrl+0)");
+                "(Ctrl+0)".to_string()
             } else {
-                return "".to_string();
+                "".to_string()
             }
         }
 
-        for i in 0..self.matching_executable_indexes.len() {
-            let index = self.matching_executable_indexes[i];
-            let executable = &self.executables[index];
-
+        let first_visible_index = self.get_first_visible_index(height);
+        for (i, executable) in self
+            .get_visible_executables(height)
+            .enumerate()
+            .map(|(row, executable)| (first_visible_index + row, executable))
+        {
             let mut text_buffer =
                 glyphon::Buffer::new(font_system, glyphon::Metrics::new(font_size, line_height));
 
             text_buffer.set_size(font_system, Some(width), Some(height));
+            // Entries that can be launched in several ways show which one is picked
+            let target_hint = if executable.get_launch_targets().count() < 2 {
+                "".to_string()
+            } else {
+                let target = self.get_launch_target(i);
+                format!(
+                    "[{} {}/{}] ",
+                    executable
+                        .get_launch_targets()
+                        .nth(target)
+                        .unwrap_or_default(),
+                    target + 1,
+                    executable.get_launch_targets().count()
+                )
+            };
             text_buffer.set_text(
                 font_system,
-                &format!("{} {}", executable.get_display_text(), get_index_hint(i)),
+                &format!(
+                    "{} {}{}",
+                    executable.get_display_text(),
+                    target_hint,
+                    get_index_hint(i)
+                ),
                 glyphon::Attrs::new().family(font),
                 glyphon::Shaping::Advanced,
             );
@@ -171,11 +551,14 @@ struct WindowState {
     atlas: glyphon::TextAtlas,
     text_renderer: glyphon::TextRenderer,
 
+    rect_renderer: RectRenderer,
+    icons: Option<(IconLoader, IconRenderer)>,
+
     window: Arc<Window>,
 }
 
 impl WindowState {
-    async fn new(window: Arc<Window>) -> Self {
+    async fn new(window: Arc<Window>, config: &Config, show_icons: bool) -> Self {
         let physical_size = window.inner_size();
 
         // Set up surface
@@ -224,6 +607,17 @@ impl WindowState {
             None,
         );
 
+        let rect_renderer = RectRenderer::new(&device, swapchain_format);
+        let icons = show_icons.then(|| {
+            let icon_size = get_icon_size(config.line_height);
+            // Icon themes only have directories for whole scale factors
+            let scale = window.scale_factor().round() as u32;
+            (
+                IconLoader::new(config.icon_theme.as_deref(), icon_size, scale),
+                IconRenderer::new(&device, swapchain_format, icon_size),
+            )
+        });
+
         Self {
             device,
             queue,
@@ -234,6 +628,8 @@ impl WindowState {
             viewport,
             atlas,
             text_renderer,
+            rect_renderer,
+            icons,
             window,
         }
     }
@@ -242,31 +638,124 @@ impl WindowState {
 struct App {
     state: AppState,
     window_state: Option<WindowState>,
+    proxy: EventLoopProxy<UserEvent>,
+    /// When the cache file was modified as of the last time it was read
+    cache_modified: Option<SystemTime>,
+}
+
+/// Events sent to the event loop from other threads
+enum UserEvent {
+    /// The background rescan found different executables than the cache had, or the cache was
+    /// read again after the watcher changed it
+    ExecutablesUpdated(Vec<Executable>),
+    /// A client asked the daemon to show or hide the window
+    Toggle,
 }
 
-impl ApplicationHandler for App {
+impl ApplicationHandler<UserEvent> for App {
+    fn user_event(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::ExecutablesUpdated(executables) => {
+                info!("Updated to {} executables", executables.len());
+                self.state.set_executables(executables);
+                if let Some(window_state) = &self.window_state {
+                    window_state.window.request_redraw();
+                }
+            }
+            UserEvent::Toggle => {
+                let Some(window_state) = &self.window_state else {
+                    return;
+                };
+                if self.state.shown {
+                    self.state.close(event_loop, &window_state.window);
+                    return;
+                }
+                // Executables may have been installed since the daemon started. A running watcher
+                // keeps the cache up-to-date, so reading it again when it changed is enough.
+                if executables::is_watcher_running(&self.state.config) {
+                    let cache_modified = executables::get_cache_modified_time(&self.state.config);
+                    if cache_modified != self.cache_modified {
+                        self.cache_modified = cache_modified;
+                        spawn_reload(&self.state.config, self.proxy.clone());
+                    }
+                } else {
+                    spawn_rescan(&self.state.config, &self.state.paths, self.proxy.clone());
+                }
+                self.state.show(&window_state.window);
+            }
+        }
+    }
+
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        // The window outlives suspends, since the daemon keeps it around
+        if self.window_state.is_some() {
+            return;
+        }
+
         let Config {
             window_width,
             window_height,
             window_pos_x,
             window_pos_y,
+            line_height,
+            lines,
+            position_bottom,
+            monitor,
             ..
         } = self.state.config;
-        info!("Creating window with size ({window_width}, {window_height}) at position ({window_pos_x}, {window_pos_y})");
+
+        // Fit the window to the search entry and the requested number of lines
+        let window_height = match lines {
+            Some(lines) => ((lines + 1) as f32 * line_height) as u32 + 20,
+            None => window_height,
+        };
+
+        let monitor = match monitor {
+            Some(index) => event_loop.available_monitors().nth(index).or_else(|| {
+                error!("Monitor {index} does not exist, using the primary monitor");
+                event_loop.primary_monitor()
+            }),
+            None => event_loop.primary_monitor(),
+        }
+        .or_else(|| event_loop.available_monitors().next());
+        let position: Position = match monitor {
+            Some(monitor) => {
+                // The configured position is relative to the monitor
+                let scale_factor = monitor.scale_factor();
+                let monitor_position = monitor.position();
+                let x = monitor_position.x + (window_pos_x as f64 * scale_factor) as i32;
+                let y = if position_bottom {
+                    monitor_position.y + monitor.size().height as i32
+                        - (window_height as f64 * scale_factor) as i32
+                } else {
+                    monitor_position.y + (window_pos_y as f64 * scale_factor) as i32
+                };
+                PhysicalPosition::new(x, y).into()
+            }
+            None => LogicalPosition::new(window_pos_x, window_pos_y).into(),
+        };
+
+        info!(
+            "Creating window with size ({window_width}, {window_height}) at position {position:?}"
+        );
         let window_attributes = Window::default_attributes()
             .with_inner_size(LogicalSize::new(window_width, window_height))
-            .with_position(LogicalPosition::new(window_pos_x, window_pos_y))
+            .with_position(position)
             .with_title("Menu Vroom")
             .with_resizable(false)
             .with_decorations(false)
-            .with_transparent(true);
+            .with_transparent(true)
+            .with_visible(self.state.shown);
         let window = Arc::new(
             event_loop
                 .create_window(window_attributes)
                 .expect("Failed to create window"),
         );
-        self.window_state = Some(pollster::block_on(WindowState::new(window)));
+        self.window_state = Some(pollster::block_on(WindowState::new(
+            window,
+            &self.state.config,
+            self.state.shows_icons(),
+        )));
     }
 
     fn window_event(
@@ -290,17 +779,19 @@ impl ApplicationHandler for App {
             viewport,
             atlas,
             text_renderer,
+            rect_renderer,
+            icons,
             ..
         } = window_state;
 
         match event {
             WindowEvent::CloseRequested => {
-                event_loop.exit();
+                self.state.cancel(event_loop, window);
             }
 
             WindowEvent::RedrawRequested => {
                 viewport.update(
-                    &queue,
+                    queue,
                     glyphon::Resolution {
                         width: surface_config.width,
                         height: surface_config.height,
@@ -316,18 +807,75 @@ impl ApplicationHandler for App {
                 let text_buffers =
                     self.state
                         .get_text_buffers(font_system, physical_width, physical_height);
+                // The first buffer is the search entry, the rest are the visible executables
+                let highlighted_buffer_index = self.state.selected_index
+                    - self.state.get_first_visible_index(physical_height)
+                    + 1;
+                let mut rects = Vec::with_capacity(1);
+                if let Some(bg_color_highlighted) = self.state.config.bg_color_highlighted {
+                    if highlighted_buffer_index < text_buffers.len() {
+                        rects.push(Rect {
+                            left: 0.0,
+                            top: 10.0
+                                + highlighted_buffer_index as f32 * self.state.config.line_height,
+                            width: surface_config.width as f32,
+                            height: self.state.config.line_height,
+                            color: bg_color_highlighted,
+                        });
+                    }
+                }
+                rect_renderer.prepare(
+                    device,
+                    queue,
+                    (surface_config.width, surface_config.height),
+                    &rects,
+                );
+
+                // Icons go in a square column left of the executables
+                let mut executables_left = 10.0;
+                if let Some((icon_loader, icon_renderer)) = icons {
+                    let line_height = self.state.config.line_height;
+                    let icon_offset = (line_height - icon_renderer.get_icon_size() as f32) / 2.0;
+                    let mut visible_icons = Vec::new();
+                    for (row, executable) in self
+                        .state
+                        .get_visible_executables(physical_height)
+                        .enumerate()
+                    {
+                        let Some(icon) = &executable.icon else {
+                            continue;
+                        };
+                        if !icon_renderer.contains(icon) {
+                            if let Some(pixels) = icon_loader.load(icon) {
+                                icon_renderer.insert(queue, icon, &pixels);
+                            }
+                        }
+                        visible_icons.push(Icon {
+                            name: icon,
+                            left: 10.0 + icon_offset,
+                            top: 10.0 + (row + 1) as f32 * line_height + icon_offset,
+                        });
+                    }
+                    icon_renderer.prepare(
+                        device,
+                        queue,
+                        (surface_config.width, surface_config.height),
+                        &visible_icons,
+                    );
+                    executables_left += line_height;
+                }
+
                 let mut top = 10.0;
-                let mut index = 0;
-                for text_buffer in &text_buffers {
-                    let color;
-                    if index - 1 == self.state.selected_index {
-                        color = self.state.config.font_color_highlighted;
+                for (index, text_buffer) in text_buffers.iter().enumerate() {
+                    let color = if index == highlighted_buffer_index {
+                        self.state.config.font_color_highlighted
                     } else {
-                        color = self.state.config.font_color;
-                    }
+                        self.state.config.font_color
+                    };
                     text_areas.push(TextArea {
                         buffer: text_buffer,
-                        left: 10.0,
+                        // The first buffer is the search entry
+                        left: if index == 0 { 10.0 } else { executables_left },
                         top,
                         scale: 1.0,
                         bounds: glyphon::TextBounds {
@@ -340,7 +888,6 @@ impl ApplicationHandler for App {
                         custom_glyphs: &[],
                     });
                     top += self.state.config.line_height;
-                    index += 1;
                 }
 
                 text_renderer
@@ -377,8 +924,12 @@ impl ApplicationHandler for App {
                     timestamp_writes: None,
                     occlusion_query_set: None,
                 });
+                rect_renderer.render(&mut pass);
+                if let Some((_, icon_renderer)) = icons {
+                    icon_renderer.render(&mut pass);
+                }
                 text_renderer
-                    .render(&atlas, &viewport, &mut pass)
+                    .render(atlas, viewport, &mut pass)
                     .expect("Failed to render text");
                 drop(pass);
 
@@ -396,19 +947,29 @@ impl ApplicationHandler for App {
                         winit::keyboard::Key::Named(NamedKey::Control) => {
                             self.state.ctrl_pressed = true;
                         }
+                        winit::keyboard::Key::Named(NamedKey::Shift) => {
+                            self.state.shift_pressed = true;
+                        }
 
+                        // Like in dmenu, Shift+Enter prints the typed text even if it matches
+                        winit::keyboard::Key::Named(NamedKey::Enter)
+                            if self.state.shift_pressed && self.state.mode == Mode::Dmenu =>
+                        {
+                            print_selection(&self.state.search_entry);
+                            self.state.close(event_loop, window);
+                        }
                         winit::keyboard::Key::Named(NamedKey::Enter) => {
-                            if let Some(executable) = self.state.get_selected_executable() {
-                                if executable.is_desktop_file() {
-                                    run_command(&executable.command);
-                                } else {
-                                    run_binary(&self.state.paths, &executable.command);
-                                }
+                            let picked = self.state.pick_or_confirm(self.state.selected_index);
+                            if picked {
+                                self.state.close(event_loop, window);
                             }
-                            event_loop.exit();
                         }
                         winit::keyboard::Key::Named(NamedKey::Escape) => {
-                            event_loop.exit();
+                            if self.state.confirming {
+                                self.state.confirming = false;
+                            } else {
+                                self.state.cancel(event_loop, window);
+                            }
                         }
 
                         winit::keyboard::Key::Named(NamedKey::ArrowUp) => {
@@ -421,34 +982,36 @@ impl ApplicationHandler for App {
                         winit::keyboard::Key::Named(NamedKey::Backspace) => {
                             self.state.search_backspace()
                         }
+                        winit::keyboard::Key::Named(NamedKey::Tab) => {
+                            self.state.cycle_launch_target()
+                        }
                         winit::keyboard::Key::Named(NamedKey::Space) => {
                             self.state.append_to_search(" ")
                         }
 
                         winit::keyboard::Key::Character(c) => {
                             if self.state.ctrl_pressed {
-                                let executable = match c.as_str() {
-                                    "1" => self.state.get_executable(0),
-                                    "2" => self.state.get_executable(1),
-                                    "3" => self.state.get_executable(2),
-                                    "4" => self.state.get_executable(3),
-                                    "5" => self.state.get_executable(4),
-                                    "6" => self.state.get_executable(5),
-                                    "7" => self.state.get_executable(6),
-                                    "8" => self.state.get_executable(7),
-                                    "9" => self.state.get_executable(8),
-                                    "0" => self.state.get_executable(9),
+                                let index = match c.as_str() {
+                                    "1" => Some(0),
+                                    "2" => Some(1),
+                                    "3" => Some(2),
+                                    "4" => Some(3),
+                                    "5" => Some(4),
+                                    "6" => Some(5),
+                                    "7" => Some(6),
+                                    "8" => Some(7),
+                                    "9" => Some(8),
+                                    "0" => Some(9),
                                     _ => None,
                                 };
-                                if let Some(executable) = executable {
-                                    if executable.is_desktop_file() {
-                                        run_command(&executable.command);
-                                    } else {
-                                        run_binary(&self.state.paths, &executable.command);
+                                if let Some(index) = index {
+                                    if self.state.get_executable(index).is_some()
+                                        && self.state.pick_or_confirm(index)
+                                    {
+                                        self.state.close(event_loop, window);
                                     }
-                                    event_loop.exit();
                                 }
-                                if c.as_str() == "i" {
+                                if c.as_str() == "i" && self.state.mode == Mode::Launcher {
                                     info!("Invalidating cache");
                                     if fs::remove_file(
                                         self.state.config.cache_dir.clone() + CACHE_FILE_NAME,
@@ -473,8 +1036,11 @@ impl ApplicationHandler for App {
                         winit::keyboard::Key::Named(NamedKey::Control) => {
                             self.state.ctrl_pressed = false;
                         }
+                        winit::keyboard::Key::Named(NamedKey::Shift) => {
+                            self.state.shift_pressed = false;
+                        }
                         _ => {}
-                    };
+                    }
                 }
             }
 
@@ -483,68 +1049,198 @@ impl ApplicationHandler for App {
     }
 }
 
-fn run_binary(directories: &Vec<String>, executable: &str) {
-    let mut command = None;
-    for dir in directories {
-        let full_path = format!("{}/{}", dir.clone(), executable);
-        if Path::new(&full_path).exists() {
-            command = Some(full_path);
-            break;
+/// Icons fill the height of a row, apart from the padding
+fn get_icon_size(line_height: f32) -> u32 {
+    (line_height - 2.0 * ICON_PADDING).max(1.0) as u32
+}
+
+/// Command line to run a program in a terminal emulator, from the config or else from
+/// `$TERMINAL` or `x-terminal-emulator`, which both take the program after `-e`
+fn get_terminal_args(config: &Config) -> Option<Vec<String>> {
+    if let Some(terminal) = &config.terminal {
+        match desktop_entry::split_exec_args(terminal) {
+            Ok(args) if !args.is_empty() => {
+                return Some(args.into_iter().map(|arg| arg.value).collect())
+            }
+            _ => error!("Malformed terminal command '{terminal}'"),
         }
     }
-    if command.is_none() {
-        error!("Failed to find executable");
-        std::process::exit(1);
-    }
 
-    run_command(&command.unwrap());
+    let terminal = env::var("TERMINAL")
+        .ok()
+        .filter(|terminal| executables::find_in_path(terminal).is_some())
+        .or_else(|| {
+            executables::find_in_path("x-terminal-emulator")
+                .map(|_| "x-terminal-emulator".to_string())
+        })?;
+    Some(vec![terminal, "-e".to_string()])
 }
 
-fn run_command(command: &str) {
-    info!("Launching: {}", command);
-    unsafe {
-        let split: Vec<&str> = command.split_whitespace().collect::<Vec<&str>>();
+/// Runs `args` in its own session, inside the terminal emulator of `terminal_args` if given.
+/// It starts in `working_dir`, or in the home directory if that is not given or does not exist.
+fn run_command(
+    args: Vec<String>,
+    terminal_args: Option<Vec<String>>,
+    working_dir: Option<&str>,
+    mut envs: HashMap<String, String>,
+) {
+    info!("Launching: {:?}", args);
 
-        let binary = split[0].to_string();
+    // Leading `NAME=value` arguments set environment variables, like they would in a shell
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next_if(|arg| desktop_entry::is_env_assignment(arg)) {
+        let (name, value) = arg.split_once('=').unwrap();
+        envs.insert(name.to_string(), value.to_string());
+    }
+    if args.peek().is_none() {
+        error!("Nothing to launch");
+        return;
+    }
+    // The environment goes to the terminal too, which passes it on to the program
+    let mut args = terminal_args.unwrap_or_default().into_iter().chain(args);
+    let binary = args.next().unwrap();
 
-        let mut envs: HashMap<String, String> = env::vars().collect();
-        let mut args: Vec<String> = Vec::with_capacity(split.len() - 1);
-        split.iter().skip(1).for_each(|value| {
-            if value.contains("=") {
-                let split = value.split_once("=").unwrap();
-                envs.insert(split.0.to_string(), split.1.to_string());
-            } else {
-                args.push(value.to_string());
+    let working_dir = working_dir
+        .filter(|dir| {
+            let exists = Path::new(dir).is_dir();
+            if !exists {
+                error!("Working directory '{dir}' does not exist");
             }
-        });
+            exists
+        })
+        .map(|dir| dir.to_string())
+        .or_else(|| env::var("HOME").ok());
 
-        let result = Command::new(binary)
-            .args(args)
-            .envs(envs)
+    let mut command = Command::new(binary);
+    command.args(args).envs(envs);
+    if let Some(working_dir) = working_dir {
+        command.current_dir(working_dir);
+    }
+    unsafe {
+        let result = command
             .pre_exec(|| {
                 nix::unistd::setsid().map_err(|_| io::Error::from(io::ErrorKind::Other))?;
                 Ok(())
             })
             .spawn();
-        if result.is_err() {
-            error!("Failed to spawn process {:?}", result.err().unwrap());
+        match result {
+            // The daemon outlives what it launches, so it has to reap them
+            Ok(mut child) => {
+                thread::spawn(move || child.wait());
+            }
+            Err(err) => error!("Failed to spawn process {:?}", err),
         }
     };
 }
 
-pub fn app_main() {
-    let config = Config::new();
-    let paths = executables::get_binary_dirs(&config);
+/// Prints the picked entry of dmenu mode
+fn print_selection(output: &str) {
+    let mut stdout = io::stdout();
+    if writeln!(stdout, "{output}")
+        .and_then(|_| stdout.flush())
+        .is_err()
+    {
+        error!("Failed to write selection to stdout");
+    }
+}
 
-    let executables = executables::get_executables_for_config_and_paths(&config, &paths);
+/// Reads newline separated entries from stdin, keeping their order
+fn read_stdin_entries() -> Vec<Executable> {
+    let mut entries = Vec::new();
+    for line_result in io::stdin().lock().lines() {
+        match line_result {
+            Ok(line) => entries.push(Executable::new_stdin_entry(line)),
+            Err(err) => {
+                error!("Failed to read entry from stdin: {:?}", err);
+                break;
+            }
+        }
+    }
+    entries
+}
 
-    let event_loop = EventLoop::new().unwrap();
+/// Rescans the directories that changed since the cache was written, and sends what it finds to
+/// the event loop if that differs from the cache
+fn spawn_rescan(
+    config: &Config,
+    paths: &[String],
+    proxy: EventLoopProxy<UserEvent>,
+) -> JoinHandle<()> {
+    let config = config.clone();
+    let paths = paths.to_vec();
+    thread::spawn(move || {
+        if let Some(executables) = executables::update_cache(&config, &paths, &HashSet::new()) {
+            // Fails only when the window is already closed
+            let _ = proxy.send_event(UserEvent::ExecutablesUpdated(executables));
+        }
+    })
+}
+
+/// Reads the cache again off the event loop, and sends its executables to the event loop
+fn spawn_reload(config: &Config, proxy: EventLoopProxy<UserEvent>) -> JoinHandle<()> {
+    let config = config.clone();
+    thread::spawn(move || {
+        if let Some(executables) = executables::read_cached_executables(&config) {
+            // Fails only when the window is already closed
+            let _ = proxy.send_event(UserEvent::ExecutablesUpdated(executables));
+        }
+    })
+}
+
+/// Runs the menu until an entry is picked or it is cancelled. As a `daemon` it runs until it is
+/// killed, and clients show and hide its window.
+pub fn app_main(mode: Mode, config: Config, daemon: bool) {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build().unwrap();
     event_loop.set_control_flow(ControlFlow::Wait);
 
+    let mut rescan = None;
+    let cache_modified = executables::get_cache_modified_time(&config);
+    let (history, paths, executables) = match mode {
+        Mode::Launcher => {
+            let history = History::load(&config.cache_dir);
+            let paths = executables::get_binary_dirs(&config);
+            // The window opens with what was found last time while the rescan runs
+            let executables = executables::read_cached_executables(&config);
+
+            // A running watcher keeps the cache up-to-date already
+            if executables.is_none() || !executables::is_watcher_running(&config) {
+                rescan = Some(spawn_rescan(&config, &paths, event_loop.create_proxy()));
+            } else {
+                info!("Watcher is running, skipping rescan");
+            }
+            (history, paths, executables.unwrap_or_default())
+        }
+        Mode::Dmenu => (History::default(), vec![], read_stdin_entries()),
+    };
+
+    if daemon {
+        let listener = daemon::listen(&config);
+        let proxy = event_loop.create_proxy();
+        thread::spawn(move || {
+            daemon::serve(listener, || {
+                let _ = proxy.send_event(UserEvent::Toggle);
+            })
+        });
+    }
+
     let mut app = App {
-        state: AppState::new(config, paths, executables),
+        state: AppState::new(mode, config, history, paths, executables, daemon),
         window_state: None,
+        proxy: event_loop.create_proxy(),
+        cache_modified,
     };
 
     event_loop.run_app(&mut app).unwrap();
+    let exit_code = app.state.exit_code;
+    // Let an unfinished rescan write the cache for next time, once the window is gone
+    drop(app);
+    if let Some(rescan) = rescan {
+        if rescan.join().is_err() {
+            error!("Rescan failed");
+        }
+    }
+
+    if exit_code != 0 {
+        process::exit(exit_code);
+    }
 }
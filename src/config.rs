@@ -1,22 +1,25 @@
-static CONFIG_FILE_SUB_PATH: &'static str = "/.config/menuvroom/config.json";
-static DEFAULT_CACHE_SUB_PATH: &'static str = "/.cache/menuvroom";
-static DEFAULT_CONFIG: &'static str = r#"
+static CONFIG_FILE_SUB_PATH: &str = "/.config/menuvroom/config.json";
+static DEFAULT_CACHE_SUB_PATH: &str = "/.cache/menuvroom";
+static DEFAULT_CONFIG: &str = r#"
 {
   "extra_directories": [],
-  "ignored_directories": [],
+  "ignored_directories": []
 }
 "#;
 
 use std::{
+    collections::HashMap,
     env, fs,
     io::{BufRead, BufReader, Write},
-    path::Path,
+    path::{Path, PathBuf},
     process,
 };
 
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 
+use crate::patterns::Pattern;
+
 #[derive(Debug, Serialize, Deserialize)]
 struct FontColor {
     r: u8,
@@ -49,15 +52,98 @@ impl BgColor {
     }
 }
 
+/// A command from the config that is listed like the scanned executables
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomEntry {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Working directory, the home directory is used when not set
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Ask before running it, for commands like shutting down
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Drops trailing and repeated slashes and `.` components, so differently written paths of a
+/// directory compare equal
+pub fn normalize_dir(dir: &str) -> String {
+    if dir.is_empty() {
+        return String::new();
+    }
+    Path::new(dir)
+        .components()
+        .collect::<PathBuf>()
+        .display()
+        .to_string()
+}
+
+/// Parses `#RGB` or `#RRGGBB` colors, as used by dmenu
+pub fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.is_ascii() {
+        return None;
+    }
+    match hex.len() {
+        3 => {
+            let mut rgb = [0; 3];
+            for (i, c) in hex.chars().enumerate() {
+                let value = c.to_digit(16)? as u8;
+                rgb[i] = value * 16 + value;
+            }
+            Some(rgb)
+        }
+        6 => {
+            let mut rgb = [0; 3];
+            for (i, value) in rgb.iter_mut().enumerate() {
+                *value = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+            }
+            Some(rgb)
+        }
+        _ => None,
+    }
+}
+
+/// Converts an sRGB color to the linear color wgpu expects for the sRGB surface
+pub fn srgb_to_wgpu_color(rgb: [u8; 3], a: f64) -> wgpu::Color {
+    fn to_linear(c: u8) -> f64 {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    wgpu::Color {
+        r: to_linear(rgb[0]),
+        g: to_linear(rgb[1]),
+        b: to_linear(rgb[2]),
+        a,
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ConfigFile {
     extra_directories: Option<Vec<String>>,
     ignored_directories: Option<Vec<String>>,
+    ignored_entries: Option<Vec<String>>,
+    include_only: Option<Vec<String>>,
+    custom_entries: Option<Vec<CustomEntry>>,
     cache_dir: Option<String>,
 
     include_binaries: Option<bool>,
     include_desktip_files: Option<bool>,
 
+    terminal: Option<String>,
+    tui_binaries: Option<Vec<String>>,
+    non_default_gpu_env: Option<HashMap<String, String>>,
+
     window_width: Option<u32>,
     window_height: Option<u32>,
     window_pos_x: Option<i32>,
@@ -65,21 +151,46 @@ struct ConfigFile {
 
     font_color: Option<FontColor>,
     font_color_highlighted: Option<FontColor>,
+    font_family: Option<String>,
     font_size: Option<f32>,
     line_height: Option<f32>,
 
     bg_color: Option<BgColor>,
+    bg_color_highlighted: Option<BgColor>,
+
+    prompt: Option<String>,
+    lines: Option<usize>,
+    case_insensitive: Option<bool>,
+    position_bottom: Option<bool>,
+    monitor: Option<usize>,
+
+    show_icons: Option<bool>,
+    icon_theme: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
     pub extra_directories: Vec<String>,
     pub ignored_directories: Vec<String>,
+    /// Entries with a binary name, desktop file ID or display name matching one of these are
+    /// not listed
+    pub ignored_entries: Vec<Pattern>,
+    /// Only entries matching one of these are listed, unless it is empty
+    pub include_only: Vec<Pattern>,
+    /// Listed along with the scanned executables, regardless of the patterns
+    pub custom_entries: Vec<CustomEntry>,
     pub cache_dir: String,
 
     pub include_binaries: bool,
     pub include_desktop_files: bool,
 
+    /// Command line of the terminal emulator, the program to run in it is appended as arguments
+    pub terminal: Option<String>,
+    /// Binaries that are run in the terminal emulator, like desktop files with `Terminal=true`
+    pub tui_binaries: Vec<String>,
+    /// Environment variables for desktop files with `PrefersNonDefaultGPU=true`
+    pub non_default_gpu_env: HashMap<String, String>,
+
     pub window_width: u32,
     pub window_height: u32,
     pub window_pos_x: i32,
@@ -87,10 +198,27 @@ pub struct Config {
 
     pub font_color: glyphon::Color,
     pub font_color_highlighted: glyphon::Color,
+    pub font_family: Option<String>,
     pub font_size: f32,
     pub line_height: f32,
 
     pub bg_color: wgpu::Color,
+    pub bg_color_highlighted: Option<wgpu::Color>,
+
+    /// Text shown in front of the search entry
+    pub prompt: Option<String>,
+    /// Maximum number of entries to list. The window height is fitted to it when set.
+    pub lines: Option<usize>,
+    pub case_insensitive: bool,
+    /// Place the window at the bottom of the monitor instead of at `window_pos_y`
+    pub position_bottom: bool,
+    /// Index of the monitor to show the window on, the primary monitor is used when not set
+    pub monitor: Option<usize>,
+
+    /// Draw the icons of desktop files left of their names
+    pub show_icons: bool,
+    /// Icon theme to look icons up in, the GTK icon theme is used when not set
+    pub icon_theme: Option<String>,
 }
 
 impl Config {
@@ -109,13 +237,12 @@ impl Config {
             info!("Config file missing, creating new config file with default contents");
 
             match config_file_path.parent() {
-                Some(parent) => match fs::create_dir_all(parent) {
-                    Err(_) => {
+                Some(parent) => {
+                    if fs::create_dir_all(parent).is_err() {
                         error!("Failed to create missing parent directory for '{config_file}'");
                         process::exit(1);
                     }
-                    _ => {}
-                },
+                }
                 None => {
                     error!("Could not get the parent directory for '{config_file}'");
                     process::exit(1);
@@ -125,6 +252,7 @@ impl Config {
             let mut file = match fs::OpenOptions::new()
                 .create(true)
                 .write(true)
+                .truncate(true)
                 .open(&config_file)
             {
                 Ok(file) => file,
@@ -134,13 +262,10 @@ impl Config {
                 }
             };
 
-            match file.write_all(DEFAULT_CONFIG.as_bytes()) {
-                Err(_) => {
-                    error!("Failed to write default contents to newly created config file");
-                    process::exit(1);
-                }
-                _ => {}
-            };
+            if file.write_all(DEFAULT_CONFIG.as_bytes()).is_err() {
+                error!("Failed to write default contents to newly created config file");
+                process::exit(1);
+            }
         }
 
         // Read and parse config
@@ -155,7 +280,6 @@ impl Config {
         let config_raw = match BufReader::new(file)
             .lines()
             .map(|l| l.unwrap())
-            .into_iter()
             .reduce(|acc, l| acc + &l)
         {
             Some(acc) => acc,
@@ -185,14 +309,46 @@ impl Config {
                 Some(cache_dir)
             })
             .unwrap();
+
+        let normalize_dirs = |dirs: Option<Vec<String>>| -> Vec<String> {
+            dirs.unwrap_or(vec![])
+                .iter()
+                .map(|dir| normalize_dir(dir))
+                .collect()
+        };
+        let parse_patterns = |patterns: Option<Vec<String>>| -> Vec<Pattern> {
+            patterns
+                .unwrap_or(vec![])
+                .iter()
+                .map(|pattern| match Pattern::parse(pattern) {
+                    Ok(pattern) => pattern,
+                    Err(err) => {
+                        error!("Invalid pattern '{pattern}': {err}");
+                        process::exit(1);
+                    }
+                })
+                .collect()
+        };
+
         Config {
-            extra_directories: config_file.extra_directories.unwrap_or(vec![]),
-            ignored_directories: config_file.ignored_directories.unwrap_or(vec![]),
+            extra_directories: normalize_dirs(config_file.extra_directories),
+            ignored_directories: normalize_dirs(config_file.ignored_directories),
+            ignored_entries: parse_patterns(config_file.ignored_entries),
+            include_only: parse_patterns(config_file.include_only),
+            custom_entries: config_file.custom_entries.unwrap_or(vec![]),
             cache_dir,
 
             include_binaries: config_file.include_binaries.unwrap_or(true),
             include_desktop_files: config_file.include_desktip_files.unwrap_or(true),
 
+            terminal: config_file.terminal,
+            tui_binaries: config_file.tui_binaries.unwrap_or(vec![]),
+            // Mesa's PRIME offloading, NVIDIA needs `__NV_PRIME_RENDER_OFFLOAD` and
+            // `__GLX_VENDOR_LIBRARY_NAME` instead
+            non_default_gpu_env: config_file
+                .non_default_gpu_env
+                .unwrap_or(HashMap::from([("DRI_PRIME".to_string(), "1".to_string())])),
+
             // These values are for a 1080p display to cover 2 thirds of the screen
             window_width: config_file.window_width.unwrap_or(1440),
             window_height: config_file.window_height.unwrap_or(810),
@@ -207,8 +363,9 @@ impl Config {
                 .font_color_highlighted
                 .map(|fc| fc.to_glyphon_color())
                 .unwrap_or(glyphon::Color::rgb(255, 0, 0)),
+            font_family: config_file.font_family,
             font_size: config_file.font_size.unwrap_or(30.0),
-            line_height: config_file.font_size.unwrap_or(42.0),
+            line_height: config_file.line_height.unwrap_or(42.0),
 
             bg_color: config_file
                 .bg_color
@@ -219,6 +376,18 @@ impl Config {
                     b: 0.15,
                     a: 0.8,
                 }),
+            bg_color_highlighted: config_file
+                .bg_color_highlighted
+                .map(|bgc| bgc.to_wgpu_color()),
+
+            prompt: config_file.prompt,
+            lines: config_file.lines,
+            case_insensitive: config_file.case_insensitive.unwrap_or(false),
+            position_bottom: config_file.position_bottom.unwrap_or(false),
+            monitor: config_file.monitor,
+
+            show_icons: config_file.show_icons.unwrap_or(true),
+            icon_theme: config_file.icon_theme,
         }
     }
 }
@@ -0,0 +1,84 @@
+static SOCKET_FILE_NAME: &str = "/menuvroom.sock";
+static TOGGLE_MESSAGE: &str = "toggle";
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    process,
+};
+
+use log::{error, info};
+
+use crate::config::Config;
+
+/// The socket lives in the runtime directory, which is private to the user and cleared on logout
+fn get_socket_path(config: &Config) -> String {
+    let dir = env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or(config.cache_dir.clone());
+    dir + SOCKET_FILE_NAME
+}
+
+/// Binds the socket that clients connect to. Exits if another daemon is listening on it already.
+pub fn listen(config: &Config) -> UnixListener {
+    let socket_path = get_socket_path(config);
+    if UnixStream::connect(&socket_path).is_ok() {
+        error!("Another daemon is already running");
+        process::exit(1);
+    }
+    // Nobody answers on a socket that is left over from a daemon that was killed
+    let _ = fs::remove_file(&socket_path);
+    if let Some(parent) = Path::new(&socket_path).parent() {
+        if let Err(err) = fs::create_dir_all(parent) {
+            error!("Failed to create directory for the socket: {:?}", err);
+        }
+    }
+
+    match UnixListener::bind(&socket_path) {
+        Ok(listener) => {
+            info!("Listening on '{socket_path}'");
+            listener
+        }
+        Err(err) => {
+            error!("Failed to bind '{socket_path}': {:?}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Calls `on_toggle` whenever a client asks to toggle the window. Never returns.
+pub fn serve(listener: UnixListener, on_toggle: impl Fn()) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                error!("Failed to accept client: {:?}", err);
+                continue;
+            }
+        };
+        let mut message = String::new();
+        if BufReader::new(stream).read_line(&mut message).is_err() {
+            error!("Failed to read message from client");
+            continue;
+        }
+        // Other daemons checking whether this one runs connect without a message
+        if message.trim_end() == TOGGLE_MESSAGE {
+            on_toggle();
+        }
+    }
+}
+
+/// Asks the running daemon to show its window, or to hide it when it is shown already. Fails
+/// when no daemon is running.
+pub fn send_toggle(config: &Config) -> Result<(), ()> {
+    let socket_path = get_socket_path(config);
+    let mut stream = UnixStream::connect(&socket_path).map_err(|_| ())?;
+    if let Err(err) = writeln!(stream, "{TOGGLE_MESSAGE}") {
+        error!("Failed to send toggle to daemon: {:?}", err);
+        return Err(());
+    }
+    Ok(())
+}
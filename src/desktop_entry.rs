@@ -0,0 +1,735 @@
+pub static DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+use std::{collections::HashMap, env, fs, path::Path};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+/// A parsed desktop entry file, see
+/// https://specifications.freedesktop.org/desktop-entry-spec/latest/
+#[derive(Debug)]
+pub struct DesktopEntry {
+    path: String,
+    /// Group name to its keys. Localized keys keep their `[locale]` suffix.
+    groups: HashMap<String, HashMap<String, String>>,
+}
+
+/// Applies the escape sequences of `string` and `localestring` values
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            // Not an escape sequence, keep it as it is
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits a list value on unescaped semicolons and applies the escape sequences of each item
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => item.push(';'),
+                Some(c) => {
+                    item.push('\\');
+                    item.push(c);
+                }
+                None => item.push('\\'),
+            },
+            ';' => items.push(unescape(&std::mem::take(&mut item))),
+            c => item.push(c),
+        }
+    }
+    // The trailing semicolon is optional
+    if !item.is_empty() {
+        items.push(unescape(&item));
+    }
+    items
+}
+
+/// The parts of a POSIX locale, `lang_COUNTRY.ENCODING@MODIFIER`, used to pick localized values
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Locale {
+    lang: String,
+    country: Option<String>,
+    modifier: Option<String>,
+}
+
+impl Locale {
+    /// Locale used for messages, from the first of `LC_ALL`, `LC_MESSAGES` and `LANG` that is set
+    pub fn from_env() -> Option<Locale> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Locale::parse(&value))
+    }
+
+    pub fn parse(locale: &str) -> Option<Locale> {
+        let (locale, modifier) = match locale.split_once('@') {
+            Some((locale, modifier)) => (locale, Some(modifier.to_string())),
+            None => (locale, None),
+        };
+        // The encoding doesn't matter, values are always UTF-8
+        let locale = locale.split('.').next().unwrap_or_default();
+        let (lang, country) = match locale.split_once('_') {
+            Some((lang, country)) => (lang, Some(country.to_string())),
+            None => (locale, None),
+        };
+
+        // These mean no localization at all
+        if lang.is_empty() || lang == "C" || lang == "POSIX" {
+            return None;
+        }
+
+        Some(Locale {
+            lang: lang.to_string(),
+            country,
+            modifier,
+        })
+    }
+
+    /// Locale suffixes to look for, from the most to the least specific, in the order the spec
+    /// defines for matching
+    fn get_candidates(&self) -> Vec<String> {
+        let mut candidates = Vec::with_capacity(4);
+        if let (Some(country), Some(modifier)) = (&self.country, &self.modifier) {
+            candidates.push(format!("{}_{}@{}", self.lang, country, modifier));
+        }
+        if let Some(country) = &self.country {
+            candidates.push(format!("{}_{}", self.lang, country));
+        }
+        if let Some(modifier) = &self.modifier {
+            candidates.push(format!("{}@{}", self.lang, modifier));
+        }
+        candidates.push(self.lang.clone());
+        candidates
+    }
+}
+
+impl DesktopEntry {
+    pub fn parse_file(path: &Path) -> Result<DesktopEntry, ()> {
+        let path_str = path.display().to_string();
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                error!("Failed to read desktop file '{path_str}': {:?}", err);
+                return Err(());
+            }
+        };
+        Ok(DesktopEntry::parse(path_str, &contents))
+    }
+
+    pub fn parse(path: String, contents: &str) -> DesktopEntry {
+        let mut groups: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut current_group: Option<String> = None;
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let Some(name) = header.strip_suffix(']') else {
+                    error!("{path}:{}: Malformed group header", line_number + 1);
+                    current_group = None;
+                    continue;
+                };
+                if groups.contains_key(name) {
+                    error!("{path}:{}: Duplicate group '{name}'", line_number + 1);
+                    current_group = None;
+                    continue;
+                }
+                groups.insert(name.to_string(), HashMap::new());
+                current_group = Some(name.to_string());
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                error!("{path}:{}: Line is not a key-value pair", line_number + 1);
+                continue;
+            };
+            let Some(group) = current_group.as_ref().and_then(|name| groups.get_mut(name)) else {
+                error!("{path}:{}: Entry outside of a valid group", line_number + 1);
+                continue;
+            };
+            // The first occurrence wins, a later one can't override it
+            group
+                .entry(key.trim_end().to_string())
+                .or_insert(value.trim_start().to_string());
+        }
+
+        DesktopEntry { path, groups }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn has_group(&self, group: &str) -> bool {
+        self.groups.contains_key(group)
+    }
+
+    /// Value of `key` without escape sequences applied
+    fn get_raw(&self, group: &str, key: &str) -> Option<&str> {
+        self.groups
+            .get(group)
+            .and_then(|keys| keys.get(key))
+            .map(|value| value.as_str())
+    }
+
+    pub fn get_string(&self, group: &str, key: &str) -> Option<String> {
+        self.get_raw(group, key).map(unescape)
+    }
+
+    pub fn get_boolean(&self, group: &str, key: &str) -> Option<bool> {
+        match self.get_raw(group, key)? {
+            "true" => Some(true),
+            "false" => Some(false),
+            value => {
+                error!("{}: '{key}' is not a boolean: '{value}'", self.path);
+                None
+            }
+        }
+    }
+
+    pub fn get_numeric(&self, group: &str, key: &str) -> Option<f64> {
+        let value = self.get_raw(group, key)?;
+        match value.parse() {
+            Ok(number) => Some(number),
+            Err(_) => {
+                error!("{}: '{key}' is not a number: '{value}'", self.path);
+                None
+            }
+        }
+    }
+
+    pub fn get_strings(&self, group: &str, key: &str) -> Option<Vec<String>> {
+        self.get_raw(group, key).map(split_list)
+    }
+
+    /// Raw value of `key` in the best matching `key[locale]` variant, or of plain `key` when
+    /// there is none for `locale`
+    fn get_locale_raw(&self, group: &str, key: &str, locale: Option<&Locale>) -> Option<&str> {
+        if let Some(locale) = locale {
+            for candidate in locale.get_candidates() {
+                if let Some(value) = self.get_raw(group, &format!("{key}[{candidate}]")) {
+                    return Some(value);
+                }
+            }
+        }
+        self.get_raw(group, key)
+    }
+
+    pub fn get_locale_string(
+        &self,
+        group: &str,
+        key: &str,
+        locale: Option<&Locale>,
+    ) -> Option<String> {
+        self.get_locale_raw(group, key, locale).map(unescape)
+    }
+
+    pub fn get_locale_strings(
+        &self,
+        group: &str,
+        key: &str,
+        locale: Option<&Locale>,
+    ) -> Option<Vec<String>> {
+        self.get_locale_raw(group, key, locale).map(split_list)
+    }
+
+    /// Arguments of the `Exec` key in `group`, with field codes expanded. Files and URLs are
+    /// never passed, so `%f %F %u %U` are dropped.
+    pub fn get_exec_args(&self, group: &str, locale: Option<&Locale>) -> Result<Vec<String>, ()> {
+        let Some(exec) = self.get_string(group, "Exec") else {
+            error!("{}: Missing Exec in '{group}'", self.path);
+            return Err(());
+        };
+
+        let mut args = Vec::new();
+        for arg in split_exec_args(&exec).map_err(|_| {
+            error!("{}: Malformed Exec '{exec}'", self.path);
+        })? {
+            if arg.quoted {
+                // Field codes are not allowed in quoted arguments
+                args.push(arg.value);
+                continue;
+            }
+            self.expand_field_codes(group, &arg.value, locale, &mut args);
+        }
+
+        if args.is_empty() {
+            error!("{}: Exec in '{group}' has no program", self.path);
+            return Err(());
+        }
+        Ok(args)
+    }
+
+    fn expand_field_codes(
+        &self,
+        group: &str,
+        arg: &str,
+        locale: Option<&Locale>,
+        args: &mut Vec<String>,
+    ) {
+        let mut expanded = String::with_capacity(arg.len());
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => expanded.push('%'),
+                // Expands to two arguments, so only when it is the whole argument
+                Some('i') if arg == "%i" => {
+                    if let Some(icon) = self.get_string(group, "Icon") {
+                        args.push("--icon".to_string());
+                        args.push(icon);
+                    }
+                    return;
+                }
+                Some('c') => {
+                    if let Some(name) = self.get_locale_string(DESKTOP_ENTRY_GROUP, "Name", locale)
+                    {
+                        expanded.push_str(&name);
+                    }
+                }
+                Some('k') => expanded.push_str(&self.path),
+                // No files or URLs are passed, and the rest are deprecated
+                Some('f' | 'F' | 'u' | 'U' | 'd' | 'D' | 'n' | 'N' | 'v' | 'm') => {
+                    // A field code on its own expands to no argument at all
+                    if arg.len() == 2 {
+                        return;
+                    }
+                }
+                Some(code) => {
+                    error!("{}: Unknown field code '%{code}' in Exec", self.path);
+                }
+                None => {
+                    error!("{}: Trailing '%' in Exec", self.path);
+                }
+            }
+        }
+        args.push(expanded);
+    }
+}
+
+pub struct ExecArg {
+    pub value: String,
+    pub quoted: bool,
+}
+
+/// Splits an `Exec` value into arguments following the quoting rules of the spec. The value must
+/// already have the general escape sequences applied.
+pub fn split_exec_args(exec: &str) -> Result<Vec<ExecArg>, ()> {
+    let mut args = Vec::new();
+    let mut chars = exec.chars().peekable();
+
+    loop {
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        let Some(c) = chars.next() else {
+            break;
+        };
+
+        // Quoted sections should wrap a whole argument, but `--option="value"` is common enough
+        // to accept as well
+        let mut value = String::new();
+        let mut quoted = false;
+        let mut next = Some(c);
+        while let Some(c) = next {
+            if c == '"' {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '`' | '$' | '\\')) => value.push(c),
+                            _ => return Err(()),
+                        },
+                        Some(c) => value.push(c),
+                        None => return Err(()),
+                    }
+                }
+            } else {
+                value.push(c);
+            }
+            next = chars.next_if(|c| *c != ' ');
+        }
+        args.push(ExecArg { value, quoted });
+    }
+
+    Ok(args)
+}
+
+/// Joins arguments into a single `Exec` style command line that `split_exec_args` turns back into
+/// the same arguments
+pub fn join_exec_args(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let needs_quoting = arg.is_empty()
+                || arg.chars().any(|c| {
+                    c.is_whitespace()
+                        || matches!(
+                            c,
+                            '"' | '\''
+                                | '\\'
+                                | '>'
+                                | '<'
+                                | '~'
+                                | '|'
+                                | '&'
+                                | ';'
+                                | '$'
+                                | '*'
+                                | '?'
+                                | '#'
+                                | '('
+                                | ')'
+                                | '`'
+                        )
+                });
+            if !needs_quoting {
+                return arg.clone();
+            }
+
+            let mut quoted = String::with_capacity(arg.len() + 2);
+            quoted.push('"');
+            for c in arg.chars() {
+                if matches!(c, '"' | '`' | '$' | '\\') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('"');
+            quoted
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Whether `arg` looks like a shell variable assignment, `NAME=value`
+pub fn is_env_assignment(arg: &str) -> bool {
+    let Some((name, _)) = arg.split_once('=') else {
+        return false;
+    };
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The program an `Exec` command line runs, past `env` and the variables it sets
+pub fn get_exec_program(exec: &str) -> Option<String> {
+    let args = split_exec_args(exec).ok()?;
+    let mut args = args.into_iter().map(|arg| arg.value).peekable();
+    args.next_if(|arg| arg == "env");
+    args.find(|arg| !is_env_assignment(arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> DesktopEntry {
+        DesktopEntry::parse("test.desktop".to_string(), contents)
+    }
+
+    fn split_values(exec: &str) -> Vec<String> {
+        split_exec_args(exec)
+            .unwrap()
+            .into_iter()
+            .map(|arg| arg.value)
+            .collect()
+    }
+
+    #[test]
+    fn unescapes_values() {
+        assert_eq!(unescape(r"a\sb\nc\td\re\\f"), "a b\nc\td\re\\f");
+        assert_eq!(unescape(r"\;\x"), r"\;\x");
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+
+    #[test]
+    fn splits_lists() {
+        assert_eq!(split_list("a;b;c;"), ["a", "b", "c"]);
+        assert_eq!(split_list("a;b"), ["a", "b"]);
+        assert_eq!(split_list(r"a\;b;c\sd;"), ["a;b", "c d"]);
+        assert_eq!(split_list(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn splits_exec_args() {
+        assert_eq!(split_values("firefox  %u"), ["firefox", "%u"]);
+        assert_eq!(
+            split_values(r#"sh -c "echo \"\$HOME\" \\ \`date\`""#),
+            ["sh", "-c", r#"echo "$HOME" \ `date`"#]
+        );
+        assert_eq!(
+            split_values(r#"app --option="a value""#),
+            ["app", "--option=a value"]
+        );
+        assert_eq!(split_values(r#"app """#), ["app", ""]);
+
+        let args = split_exec_args(r#"app "%f" %f"#).unwrap();
+        assert!(args[1].quoted);
+        assert!(!args[2].quoted);
+
+        assert!(split_exec_args(r#"app "unterminated"#).is_err());
+        assert!(split_exec_args(r#"app "\n""#).is_err());
+    }
+
+    #[test]
+    fn joined_exec_args_split_into_the_same_args() {
+        let args: Vec<String> = [
+            "app",
+            "",
+            "two words",
+            r#"quote " and backslash \"#,
+            "$HOME `date` ~ | & ; * ? # ( ) < > '",
+            "tab\there",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        assert_eq!(split_values(&join_exec_args(&args)), args);
+        assert_eq!(join_exec_args(&["plain".to_string()]), "plain");
+    }
+
+    #[test]
+    fn expands_field_codes() {
+        let entry = parse(
+            "[Desktop Entry]\n\
+             Name=Viewer\n\
+             Icon=viewer-icon\n\
+             Exec=viewer %U --name=%c %i --file %k 100%% \"%f\"\n",
+        );
+        assert_eq!(
+            entry.get_exec_args(DESKTOP_ENTRY_GROUP, None).unwrap(),
+            [
+                "viewer",
+                "--name=Viewer",
+                "--icon",
+                "viewer-icon",
+                "--file",
+                "test.desktop",
+                "100%",
+                "%f",
+            ]
+        );
+
+        let entry = parse("[Desktop Entry]\nExec=viewer %i\n");
+        assert_eq!(
+            entry.get_exec_args(DESKTOP_ENTRY_GROUP, None).unwrap(),
+            ["viewer"]
+        );
+
+        assert!(parse("[Desktop Entry]\nExec=%U\n")
+            .get_exec_args(DESKTOP_ENTRY_GROUP, None)
+            .is_err());
+        assert!(parse("[Desktop Entry]\nName=Viewer\n")
+            .get_exec_args(DESKTOP_ENTRY_GROUP, None)
+            .is_err());
+    }
+
+    #[test]
+    fn parses_groups_and_keys() {
+        let entry = parse(
+            "# Comment\n\
+             \n\
+             [Desktop Entry]\n\
+             Name = Viewer\n\
+             Name=Ignored\n\
+             Comment=View\\sthings\n\
+             not a key value pair\n\
+             [Desktop Action new-window]\n\
+             Name=New Window\n",
+        );
+        assert_eq!(
+            entry.get_string(DESKTOP_ENTRY_GROUP, "Name").as_deref(),
+            Some("Viewer")
+        );
+        assert_eq!(
+            entry.get_string(DESKTOP_ENTRY_GROUP, "Comment").as_deref(),
+            Some("View things")
+        );
+        assert_eq!(
+            entry
+                .get_string("Desktop Action new-window", "Name")
+                .as_deref(),
+            Some("New Window")
+        );
+        assert_eq!(entry.get_string(DESKTOP_ENTRY_GROUP, "Missing"), None);
+        assert_eq!(entry.get_string("Missing", "Name"), None);
+    }
+
+    #[test]
+    fn skips_duplicate_and_malformed_groups() {
+        let entry = parse(
+            "Name=Outside\n\
+             [Desktop Entry]\n\
+             Name=First\n\
+             [Desktop Entry]\n\
+             Name=Duplicate\n\
+             Icon=duplicate-icon\n\
+             [Broken\n\
+             Exec=broken\n\
+             [Other]\n\
+             Name=Other\n",
+        );
+        assert_eq!(
+            entry.get_string(DESKTOP_ENTRY_GROUP, "Name").as_deref(),
+            Some("First")
+        );
+        assert_eq!(entry.get_string(DESKTOP_ENTRY_GROUP, "Icon"), None);
+        assert_eq!(entry.get_string(DESKTOP_ENTRY_GROUP, "Exec"), None);
+        assert!(!entry.has_group("Broken"));
+        assert!(entry.has_group("Other"));
+    }
+
+    #[test]
+    fn reads_typed_values() {
+        let entry = parse(
+            "[Desktop Entry]\n\
+             Terminal=true\n\
+             NoDisplay=false\n\
+             Hidden=yes\n\
+             X-Scale=1.5\n\
+             X-Size=big\n\
+             Categories=Graphics;Viewer;\n",
+        );
+        assert_eq!(
+            entry.get_boolean(DESKTOP_ENTRY_GROUP, "Terminal"),
+            Some(true)
+        );
+        assert_eq!(
+            entry.get_boolean(DESKTOP_ENTRY_GROUP, "NoDisplay"),
+            Some(false)
+        );
+        assert_eq!(entry.get_boolean(DESKTOP_ENTRY_GROUP, "Hidden"), None);
+        assert_eq!(entry.get_numeric(DESKTOP_ENTRY_GROUP, "X-Scale"), Some(1.5));
+        assert_eq!(entry.get_numeric(DESKTOP_ENTRY_GROUP, "X-Size"), None);
+        assert_eq!(
+            entry.get_strings(DESKTOP_ENTRY_GROUP, "Categories"),
+            Some(vec!["Graphics".to_string(), "Viewer".to_string()])
+        );
+    }
+
+    #[test]
+    fn finds_exec_programs() {
+        assert_eq!(get_exec_program("firefox %u").as_deref(), Some("firefox"));
+        assert_eq!(
+            get_exec_program("env GDK_BACKEND=x11 _A1=b gimp %U").as_deref(),
+            Some("gimp")
+        );
+        assert_eq!(get_exec_program("env").as_deref(), None);
+        assert!(is_env_assignment("GDK_BACKEND=x11"));
+        assert!(!is_env_assignment("1A=b"));
+        assert!(!is_env_assignment("--option=value"));
+    }
+
+    fn locale(locale: &str) -> Locale {
+        Locale::parse(locale).unwrap()
+    }
+
+    #[test]
+    fn parses_locales() {
+        assert_eq!(
+            locale("de_DE.UTF-8@euro"),
+            Locale {
+                lang: "de".to_string(),
+                country: Some("DE".to_string()),
+                modifier: Some("euro".to_string()),
+            }
+        );
+        assert_eq!(
+            locale("tr_TR"),
+            Locale {
+                lang: "tr".to_string(),
+                country: Some("TR".to_string()),
+                modifier: None,
+            }
+        );
+        assert_eq!(
+            locale("sr@latin"),
+            Locale {
+                lang: "sr".to_string(),
+                country: None,
+                modifier: Some("latin".to_string()),
+            }
+        );
+        assert_eq!(Locale::parse("C.UTF-8"), None);
+        assert_eq!(Locale::parse("C"), None);
+        assert_eq!(Locale::parse("POSIX"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn orders_locale_candidates_like_the_spec() {
+        assert_eq!(
+            locale("de_DE.UTF-8@euro").get_candidates(),
+            ["de_DE@euro", "de_DE", "de@euro", "de"]
+        );
+        assert_eq!(locale("tr_TR").get_candidates(), ["tr_TR", "tr"]);
+        assert_eq!(locale("sr@latin").get_candidates(), ["sr@latin", "sr"]);
+        assert_eq!(locale("fr").get_candidates(), ["fr"]);
+    }
+
+    #[test]
+    fn picks_the_best_localized_value() {
+        let entry = parse(
+            "[Desktop Entry]\n\
+             Name=Files\n\
+             Name[de]=Dateien\n\
+             Name[de@euro]=Dateien (euro)\n\
+             Name[de_AT]=Dateien (AT)\n\
+             Name[tr]=Dosyalar\n\
+             Keywords=folder;\n\
+             Keywords[de]=Ordner;Verzeichnis;\n",
+        );
+        let name = |locale: Option<&Locale>| {
+            entry
+                .get_locale_string(DESKTOP_ENTRY_GROUP, "Name", locale)
+                .unwrap()
+        };
+        assert_eq!(name(Some(&locale("de_DE.UTF-8@euro"))), "Dateien (euro)");
+        assert_eq!(name(Some(&locale("de_AT.UTF-8@euro"))), "Dateien (AT)");
+        assert_eq!(name(Some(&locale("de_DE.UTF-8"))), "Dateien");
+        assert_eq!(name(Some(&locale("tr_TR"))), "Dosyalar");
+        assert_eq!(name(Some(&locale("ja_JP.UTF-8"))), "Files");
+        assert_eq!(name(Locale::parse("C.UTF-8").as_ref()), "Files");
+        assert_eq!(
+            entry.get_locale_strings(DESKTOP_ENTRY_GROUP, "Keywords", Some(&locale("de_CH"))),
+            Some(vec!["Ordner".to_string(), "Verzeichnis".to_string()])
+        );
+        assert_eq!(
+            entry.get_locale_strings(DESKTOP_ENTRY_GROUP, "Keywords", None),
+            Some(vec!["folder".to_string()])
+        );
+    }
+}
@@ -1,58 +1,113 @@
-pub static CACHE_FILE_NAME: &'static str = "/executables.txt";
+pub static CACHE_FILE_NAME: &str = "/executables.json";
+pub static CACHE_LOCK_FILE_NAME: &str = "/executables.lock";
+pub static WATCHER_LOCK_FILE_NAME: &str = "/watcher.lock";
+/// Bump when executables get data that older caches don't have, so those are rebuilt
+const CACHE_VERSION: u32 = 4;
 
 use std::{
+    collections::{HashMap, HashSet},
     env,
     fs::{self, File},
-    io::{BufRead, BufReader, Write},
-    os::unix::fs::{MetadataExt, PermissionsExt},
-    path::Path,
+    io::Write,
+    path::{Path, PathBuf},
     process,
+    time::SystemTime,
 };
 
 use log::{error, info};
-use regex::Regex;
+use nix::{
+    errno::Errno,
+    fcntl::{AtFlags, Flock, FlockArg},
+    unistd::{faccessat, AccessFlags},
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::config::Config;
+use crate::config::{normalize_dir, Config, CustomEntry};
+use crate::desktop_entry::{self, join_exec_args, DesktopEntry, Locale, DESKTOP_ENTRY_GROUP};
+use crate::patterns;
 
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub struct Executable {
     pub command: String,
     pub display_name: Option<String>,
+    /// Needs to be run in a terminal emulator, from `Terminal=true` in its desktop file
+    #[serde(default)]
+    pub terminal: bool,
+    /// Working directory from `Path=` in its desktop file
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    /// Should run on the discrete GPU, from `PrefersNonDefaultGPU=true` in its desktop file
+    #[serde(default)]
+    pub prefers_non_default_gpu: bool,
+    /// Searched along with the name, from `Keywords`, `GenericName` and `Comment` in its
+    /// desktop file
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub generic_name: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Icon name or path from `Icon=` in its desktop file
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Full path of a binary, in the first directory of PATH that has it
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Copies of a binary in later directories of PATH, which it shadows
+    #[serde(default)]
+    pub shadowed_paths: Vec<String>,
+    /// Comes from a `Desktop Action` group rather than the application itself
+    #[serde(default)]
+    pub is_action: bool,
+    /// Display name with what tells it apart from other entries with the same name
+    #[serde(skip)]
+    pub disambiguated_name: Option<String>,
+    /// The config entry it comes from, those are not cached
+    #[serde(skip)]
+    pub custom: Option<CustomEntry>,
 }
 
+/// Entries are told apart by what they show and what they run, so different entries with the
+/// same name are all kept
 impl PartialEq for Executable {
     fn eq(&self, other: &Self) -> bool {
-        self.get_display_text().eq(other.get_display_text())
+        self.get_display_text() == other.get_display_text()
+            && self.get_history_key() == other.get_history_key()
     }
 }
 
 impl Ord for Executable {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.get_display_text().cmp(other.get_display_text())
+        self.get_display_text()
+            .cmp(other.get_display_text())
+            .then_with(|| self.get_history_key().cmp(&other.get_history_key()))
     }
 }
 
 impl PartialOrd for Executable {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.get_display_text()
-            .partial_cmp(other.get_display_text())
-    }
-}
-
-impl ToString for Executable {
-    fn to_string(&self) -> String {
-        match &self.display_name {
-            Some(display_name) => format!("D:{} - {}", display_name, self.command),
-            None => self.command.clone(),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Executable {
-    fn new_binary(binary_name: String) -> Executable {
+    fn new_binary(binary_name: String, path: String) -> Executable {
         Executable {
             command: binary_name,
             display_name: None,
+            terminal: false,
+            working_dir: None,
+            prefers_non_default_gpu: false,
+            keywords: vec![],
+            generic_name: None,
+            comment: None,
+            icon: None,
+            path: Some(path),
+            shadowed_paths: vec![],
+            is_action: false,
+            disambiguated_name: None,
+            custom: None,
         }
     }
 
@@ -60,21 +115,162 @@ impl Executable {
         Executable {
             command,
             display_name: Some(display_name),
+            terminal: false,
+            working_dir: None,
+            prefers_non_default_gpu: false,
+            keywords: vec![],
+            generic_name: None,
+            comment: None,
+            icon: None,
+            path: None,
+            shadowed_paths: vec![],
+            is_action: false,
+            disambiguated_name: None,
+            custom: None,
+        }
+    }
+
+    fn new_custom(entry: &CustomEntry) -> Executable {
+        Executable {
+            working_dir: entry.cwd.clone(),
+            keywords: entry.keywords.clone(),
+            custom: Some(entry.clone()),
+            ..Executable::new_desktop_file(entry.command.clone(), entry.name.clone())
+        }
+    }
+
+    /// Entry read from stdin in dmenu mode. It is printed back instead of being launched.
+    pub fn new_stdin_entry(text: String) -> Executable {
+        Executable {
+            command: text,
+            display_name: None,
+            terminal: false,
+            working_dir: None,
+            prefers_non_default_gpu: false,
+            keywords: vec![],
+            generic_name: None,
+            comment: None,
+            icon: None,
+            path: None,
+            shadowed_paths: vec![],
+            is_action: false,
+            disambiguated_name: None,
+            custom: None,
         }
     }
 
     pub fn get_display_text(&self) -> &str {
-        match &self.display_name {
-            Some(display_name) => display_name,
-            None => &self.command,
+        match (&self.disambiguated_name, &self.display_name) {
+            (Some(disambiguated_name), _) => disambiguated_name,
+            (None, Some(display_name)) => display_name,
+            (None, None) => &self.command,
+        }
+    }
+
+    /// Identifies the executable in the launch history
+    pub fn get_history_key(&self) -> String {
+        match (&self.custom, &self.display_name) {
+            (Some(custom), _) => format!("C:{} - {}", custom.name, self.command),
+            (None, Some(display_name)) => format!("D:{} - {}", display_name, self.command),
+            (None, None) => self.command.clone(),
         }
     }
 
+    /// Texts besides the display text that a search can match. Desktop files linked to a binary
+    /// can still be found by the name of the binary.
+    pub fn get_secondary_search_texts(&self) -> impl Iterator<Item = &str> {
+        let binary_name = self
+            .path
+            .as_deref()
+            .filter(|_| self.display_name.is_some())
+            .and_then(|path| Path::new(path).file_name())
+            .and_then(|name| name.to_str());
+        self.keywords
+            .iter()
+            .chain(&self.generic_name)
+            .chain(&self.comment)
+            .map(|text| text.as_str())
+            .chain(binary_name)
+    }
+
     pub fn is_desktop_file(&self) -> bool {
-        self.display_name.is_some()
+        self.display_name.is_some() && self.custom.is_none()
+    }
+
+    /// Paths of its binary, the one that PATH picks first. Desktop files have them when their
+    /// Exec runs a binary that was found.
+    pub fn get_binary_paths(&self) -> impl Iterator<Item = &str> {
+        self.path
+            .iter()
+            .chain(&self.shadowed_paths)
+            .map(|path| path.as_str())
+    }
+
+    /// Ways to launch the executable, the Exec command line of a desktop file followed by the
+    /// paths of its binary
+    pub fn get_launch_targets(&self) -> impl Iterator<Item = &str> {
+        self.is_desktop_file()
+            .then_some(self.command.as_str())
+            .into_iter()
+            .chain(self.get_binary_paths())
+    }
+}
+
+/// How a directory is scanned, which decides what it contributes
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum DirectoryKind {
+    /// An `applications` directory or one below it. The IDs of its desktop files start with
+    /// `id_prefix`, its path relative to the `applications` directory.
+    Applications { id_prefix: String },
+    /// A directory from PATH or `extra_directories`
+    Binaries {
+        include_binaries: bool,
+        include_desktop_files: bool,
+    },
+}
+
+/// What was found in a directory, cached until the directory is modified
+#[derive(Debug, Serialize, Deserialize)]
+struct ScannedDirectory {
+    path: String,
+    kind: DirectoryKind,
+    /// When the directory was last modified at the time of the scan
+    modified: Option<SystemTime>,
+    /// Entries of each desktop file by ID. Hidden desktop files have none, but still shadow the
+    /// desktop files with the same ID in later directories.
+    desktop_files: Vec<(String, Vec<Executable>)>,
+    binaries: Vec<Executable>,
+}
+
+impl ScannedDirectory {
+    fn is_up_to_date(&self, path: &str, kind: &DirectoryKind) -> bool {
+        self.path == path && self.kind == *kind && self.modified == get_modified_time(path)
     }
 }
 
+fn get_modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Contents of the cache file
+#[derive(Serialize, Deserialize)]
+struct Cache<D> {
+    version: u32,
+    /// Desktop files are scanned for this environment, a different one needs a new scan
+    context: DesktopFileContext,
+    directories: D,
+}
+
+/// Just the version of the cache file, which can be read even when the rest has changed
+#[derive(Deserialize)]
+struct CacheVersion {
+    version: u32,
+}
+
+/// Directories of PATH followed by the extra directories. PATH's order is kept since it decides
+/// which of the binaries with the same name runs.
 pub fn get_binary_dirs(config: &Config) -> Vec<String> {
     let path_var = match env::var("PATH") {
         Ok(var) => var,
@@ -86,167 +282,518 @@ pub fn get_binary_dirs(config: &Config) -> Vec<String> {
 
     let paths: Vec<String> = path_var
         .split(":")
-        .map(|entry| entry.to_string())
+        .map(normalize_dir)
         .filter(|path| fs::exists(path).unwrap_or(false))
-        .filter(|path| !config.ignored_directories.contains(&path))
+        .filter(|path| !config.ignored_directories.contains(path))
         .collect();
     let paths = [paths, config.extra_directories.clone()].concat();
 
-    let mut paths = paths
-        .iter()
-        .map(|path| path.to_string())
-        .collect::<Vec<String>>();
-    paths.sort();
-    paths.dedup();
+    // Only the first of the entries for a directory counts, including ones through symlinks
+    // like /bin for /usr/bin
+    let mut seen_dirs = HashSet::new();
     paths
+        .into_iter()
+        .filter(|path| seen_dirs.insert(fs::canonicalize(path).unwrap_or(PathBuf::from(path))))
+        .collect()
 }
 
-fn should_invalidate_cache(config: &Config, executable_dirs: &Vec<String>) -> bool {
-    let cache_file = config.cache_dir.clone() + CACHE_FILE_NAME;
-    let cache_file_path = Path::new(&cache_file);
-
-    // If cache files does not exist create it
-    if !cache_file_path.exists() {
-        info!("Cache file does not exist, creating a new one");
-        match cache_file_path.parent() {
-            Some(parent) => match fs::create_dir_all(parent) {
-                Err(_) => {
-                    error!("Failed to create missing parent directory for '{cache_file}'");
-                    process::exit(1);
+/// `XDG_DATA_HOME` followed by the `XDG_DATA_DIRS`, most important first
+pub fn get_data_dirs() -> Vec<String> {
+    let data_home = env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .or_else(|| env::var("HOME").ok().map(|home| home + "/.local/share"));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or("/usr/local/share:/usr/share".to_string());
+
+    data_home
+        .iter()
+        .map(|dir| dir.as_str())
+        .chain(data_dirs.split(':'))
+        .map(normalize_dir)
+        .collect()
+}
+
+/// Directories with the desktop files of applications, from the most to the least important. A
+/// desktop file in one of them shadows desktop files with the same ID in the ones after it.
+pub fn get_application_dirs(config: &Config) -> Vec<String> {
+    let mut application_dirs: Vec<String> = Vec::new();
+    for data_dir in get_data_dirs() {
+        let application_dir = data_dir + "/applications";
+        if !application_dirs.contains(&application_dir)
+            && fs::exists(&application_dir).unwrap_or(false)
+            && !config.ignored_directories.contains(&application_dir)
+        {
+            application_dirs.push(application_dir);
+        }
+    }
+    application_dirs
+}
+
+/// Every directory to scan in the order they take precedence, application directories with
+/// everything below them first
+fn get_directories_to_scan(config: &Config, paths: &[String]) -> Vec<(String, DirectoryKind)> {
+    let mut directories = Vec::new();
+    if config.include_desktop_files {
+        for application_dir in get_application_dirs(config) {
+            let mut dirs = vec![(application_dir, String::new())];
+            let mut i = 0;
+            while i < dirs.len() {
+                if let Ok(entries) = fs::read_dir(&dirs[i].0) {
+                    for entry in entries.flatten() {
+                        // Symlinked directories are not followed so they can't form cycles
+                        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                            let name = entry.file_name().to_string_lossy().to_string();
+                            let id_prefix = format!("{}{name}-", dirs[i].1);
+                            dirs.push((entry.path().display().to_string(), id_prefix));
+                        }
+                    }
                 }
-                _ => {}
-            },
-            None => {
-                error!("Could not get parent directory for '{cache_file}'");
-                process::exit(1);
+                i += 1;
             }
+            directories.extend(
+                dirs.into_iter()
+                    .map(|(dir, id_prefix)| (dir, DirectoryKind::Applications { id_prefix })),
+            );
         }
-        match fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&cache_file)
-        {
-            Err(err) => {
-                error!("{:?}", err);
-                error!("Failed to create missing cache file '{cache_file}'");
-                process::exit(1);
-            }
-            _ => {}
-        };
-        return true;
     }
 
-    // If cache file is not up-to-date
-    let cache_last_update_time = cache_file_path.metadata().unwrap().mtime();
-    for executable_dir in executable_dirs {
-        let path = Path::new(executable_dir);
-        if !path.exists() {
-            continue;
-        }
-        if path.metadata().unwrap().mtime() > cache_last_update_time {
-            info!("Cache file is not up-to-date");
-            return true;
-        }
+    for path in paths {
+        directories.push((
+            path.clone(),
+            DirectoryKind::Binaries {
+                include_binaries: config.include_binaries,
+                include_desktop_files: config.include_desktop_files,
+            },
+        ));
     }
+    directories
+}
 
-    info!("Cache file is up-to-date");
-    false
+/// Executables in the cache as it is, which may be out of date. None if there is no usable
+/// cache. A running watcher owns the cache, so its cache is used even when the watcher runs in
+/// another environment, rather than both rebuilding it for their own.
+pub fn read_cached_executables(config: &Config) -> Option<Vec<Executable>> {
+    let context = DesktopFileContext::from_env();
+    let directories = read_cache_file(config, &context, is_watcher_running(config)).ok()?;
+    Some(merge_directories(config, &directories))
+}
+
+/// When the cache file was last written, to tell whether it changed since it was read
+pub fn get_cache_modified_time(config: &Config) -> Option<SystemTime> {
+    get_modified_time(&(config.cache_dir.clone() + CACHE_FILE_NAME))
+}
+
+/// Every directory that is scanned for executables
+pub fn get_scanned_dirs(config: &Config, paths: &[String]) -> Vec<String> {
+    get_directories_to_scan(config, paths)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
 }
 
-pub fn get_executables_for_config_and_paths(
+/// Rescans only the directories that changed since the cache was written and updates it.
+/// Directories in `changed_dirs` are rescanned as well, for changes that don't affect their
+/// modification time, like a file being edited. Directories that are no longer scanned are
+/// dropped from the cache. Returns `None` if the cache was already up-to-date.
+pub fn update_cache(
     config: &Config,
-    paths: &Vec<String>,
-) -> Vec<Executable> {
-    let mut executables: Vec<Executable>;
-    if should_invalidate_cache(&config, &paths) {
-        executables = paths
-            .iter()
-            .map(|path| {
-                get_executables_from_directory(
-                    path,
-                    config.include_binaries,
-                    config.include_desktop_files,
-                )
-            })
-            .filter(|executables_result| executables_result.is_ok())
-            .map(|executable_result| executable_result.unwrap())
-            .flatten()
-            .collect();
-        executables.sort();
-        executables.dedup();
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .open(config.cache_dir.clone() + CACHE_FILE_NAME)
-            .unwrap();
-        match file.write_all(
-            executables
+    paths: &[String],
+    changed_dirs: &HashSet<String>,
+) -> Option<Vec<Executable>> {
+    let directories = get_directories_to_scan(config, paths);
+    let context = DesktopFileContext::from_env();
+
+    let is_fresh = |scanned: &ScannedDirectory, path: &str, kind: &DirectoryKind| {
+        scanned.is_up_to_date(path, kind) && !changed_dirs.contains(path)
+    };
+    let is_up_to_date = |cached: &[ScannedDirectory]| {
+        cached.len() == directories.len()
+            && cached
                 .iter()
-                .map(|e| e.to_string())
-                .collect::<Vec<String>>()
-                .join("\n")
-                .as_bytes(),
-        ) {
-            Err(_) => {
-                error!("Failed to update cache file");
-                process::exit(1);
-            }
-            _ => {}
-        };
-    } else {
-        executables = vec![];
-        let file = File::open(config.cache_dir.clone() + CACHE_FILE_NAME).unwrap();
-        for line_result in BufReader::new(file).lines() {
-            let entry = line_result;
-            if entry.is_err() {
-                error!("{:?}", entry.err());
-                continue;
+                .zip(&directories)
+                .all(|(scanned, (path, kind))| is_fresh(scanned, path, kind))
+    };
+
+    let cached = read_cache_file(config, &context, false).unwrap_or_default();
+    if is_up_to_date(&cached) {
+        info!("Cache file is up-to-date");
+        return None;
+    }
+
+    // Held until the new cache is written, so only one instance rebuilds it at a time
+    let _lock = lock_cache(config);
+    // Another instance may have updated it while this one waited for the lock
+    let cached = read_cache_file(config, &context, false).unwrap_or_default();
+    if is_up_to_date(&cached) {
+        return Some(merge_directories(config, &cached));
+    }
+
+    info!("Reading desktop files for {:?}", context);
+    let mut cached: HashMap<(String, DirectoryKind), ScannedDirectory> = cached
+        .into_iter()
+        .map(|scanned| ((scanned.path.clone(), scanned.kind.clone()), scanned))
+        .collect();
+    let directories: Vec<(String, DirectoryKind, Option<ScannedDirectory>)> = directories
+        .into_iter()
+        .map(|(path, kind)| {
+            let fresh = cached
+                .remove(&(path.clone(), kind.clone()))
+                .filter(|scanned| is_fresh(scanned, &path, &kind));
+            (path, kind, fresh)
+        })
+        .collect();
+    // Most of a scan is waiting on the file system, so the directories are scanned in parallel
+    let scanned: Vec<ScannedDirectory> = directories
+        .into_par_iter()
+        .map(|(path, kind, fresh)| fresh.unwrap_or_else(|| scan_directory(path, kind, &context)))
+        .collect();
+
+    if write_cache_file(config, &context, &scanned).is_err() {
+        error!("Failed to update cache file");
+    }
+    Some(merge_directories(config, &scanned))
+}
+
+/// Executables of all directories, without the desktop files shadowed by earlier directories.
+/// Binaries shadowed by earlier directories are kept on the binary that shadows them. Entries
+/// are filtered here rather than when scanning, so changing the patterns needs no rescan. The
+/// custom entries of the config are added as well.
+fn merge_directories(config: &Config, directories: &[ScannedDirectory]) -> Vec<Executable> {
+    let is_included = |names: &[&str]| {
+        patterns::is_included(&config.ignored_entries, &config.include_only, names)
+    };
+
+    let mut desktop_file_ids = HashSet::new();
+    // Entries of desktop files with the ID of their desktop file
+    let mut desktop_entries: Vec<(&str, Executable)> = vec![];
+    let mut binary_indexes: HashMap<&str, usize> = HashMap::new();
+    let mut binaries: Vec<Executable> = vec![];
+    for directory in directories {
+        for (desktop_file_id, desktop_executables) in &directory.desktop_files {
+            if desktop_file_ids.insert(desktop_file_id) {
+                desktop_entries.extend(
+                    desktop_executables
+                        .iter()
+                        .filter(|executable| {
+                            is_included(&[desktop_file_id, executable.get_display_text()])
+                        })
+                        .map(|executable| (desktop_file_id.as_str(), executable.clone())),
+                );
+            } else {
+                info!(
+                    "Desktop file '{desktop_file_id}' in '{}' is shadowed",
+                    directory.path
+                );
             }
-            let entry = entry.unwrap();
-            if entry.starts_with("D:") {
-                let cached_info = entry.split(":").nth(1);
-                if cached_info.is_none() {
-                    error!("Cache entry for desktop file is corrupt: {entry}");
-                    continue;
-                }
-                let executable_data: Vec<&str> = cached_info.unwrap().split(" - ").collect();
-                if executable_data.len() != 2 {
-                    error!("Cache entry for desktop file is corrupt: {entry}");
-                    continue;
+        }
+        for binary in directory
+            .binaries
+            .iter()
+            .filter(|binary| is_included(&[&binary.command]))
+        {
+            match binary_indexes.get(binary.command.as_str()) {
+                Some(index) => binaries[*index]
+                    .shadowed_paths
+                    .extend(binary.path.iter().cloned()),
+                None => {
+                    binary_indexes.insert(&binary.command, binaries.len());
+                    binaries.push(binary.clone());
                 }
-                executables.push(Executable::new_desktop_file(
-                    executable_data.get(1).unwrap().to_string(),
-                    executable_data.get(0).unwrap().to_string(),
-                ));
-                continue;
             }
-            if entry.contains(' ') {
-                error!("Entry '{entry}' contains spaces.");
-                continue;
-            }
-            executables.push(Executable::new_binary(entry));
         }
     }
 
+    let linked_binaries = link_binaries(&mut desktop_entries, &binaries, &binary_indexes);
+    let mut executables: Vec<(Option<&str>, Executable)> = desktop_entries
+        .into_iter()
+        .map(|(desktop_file_id, executable)| (Some(desktop_file_id), executable))
+        .chain(
+            binaries
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| !linked_binaries.contains(index))
+                .map(|(_, binary)| (None, binary)),
+        )
+        .chain(
+            config
+                .custom_entries
+                .iter()
+                .map(|entry| (None, Executable::new_custom(entry))),
+        )
+        .collect();
+    disambiguate(&mut executables);
+
+    let mut executables: Vec<Executable> = executables
+        .into_iter()
+        .map(|(_, executable)| executable)
+        .collect();
+    executables.sort();
+    executables.dedup();
     executables
 }
 
-fn get_executables_from_directory(
-    dir: &str,
-    include_binaries: bool,
-    include_desktop_files: bool,
-) -> Result<Vec<Executable>, ()> {
-    info!("Collecting from dir: {}", dir);
+/// Gives the desktop file of an application the paths of the binary its Exec runs, so they are
+/// listed as one entry that can launch either. Binaries that run several applications, like
+/// `flatpak`, or whose name has nothing to do with the application, like `sh`, are not linked.
+/// Returns the indexes of the linked binaries.
+fn link_binaries(
+    desktop_entries: &mut [(&str, Executable)],
+    binaries: &[Executable],
+    binary_indexes: &HashMap<&str, usize>,
+) -> HashSet<usize> {
+    let mut entries_by_binary: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (entry_index, (desktop_file_id, entry)) in desktop_entries.iter().enumerate() {
+        if entry.is_action {
+            continue;
+        }
+        let Some(program) = desktop_entry::get_exec_program(&entry.command) else {
+            continue;
+        };
+        let Some(binary_name) = Path::new(&program).file_name() else {
+            continue;
+        };
+        let binary_name = binary_name.to_string_lossy();
+        let Some(binary_index) = binary_indexes.get(binary_name.as_ref()) else {
+            continue;
+        };
+        // A path may point at another binary with the same name
+        if program.contains('/') && !runs_binary(&program, &binaries[*binary_index]) {
+            continue;
+        }
+        if names_match(&binary_name, desktop_file_id, entry.get_display_text()) {
+            entries_by_binary
+                .entry(*binary_index)
+                .or_default()
+                .push(entry_index);
+        }
+    }
+
+    let mut linked_binaries = HashSet::new();
+    for (binary_index, entry_indexes) in entries_by_binary {
+        let [entry_index] = entry_indexes[..] else {
+            continue;
+        };
+        let binary = &binaries[binary_index];
+        let entry = &mut desktop_entries[entry_index].1;
+        info!(
+            "Linked '{}' to binary '{}'",
+            entry.get_display_text(),
+            binary.command
+        );
+        entry.path = binary.path.clone();
+        entry.shadowed_paths = binary.shadowed_paths.clone();
+        linked_binaries.insert(binary_index);
+    }
+    linked_binaries
+}
+
+/// Whether the `program` path is one of the copies of `binary`
+fn runs_binary(program: &str, binary: &Executable) -> bool {
+    let Ok(program) = fs::canonicalize(program) else {
+        return false;
+    };
+    binary
+        .get_binary_paths()
+        .any(|path| fs::canonicalize(path).is_ok_and(|path| path == program))
+}
+
+/// Whether the first word of a binary's name is a word of the application's name or of the last
+/// part of its desktop file ID, like `gimp-2.10` and `gimp.desktop`, or `nautilus` and
+/// `org.gnome.Nautilus.desktop`
+fn names_match(binary_name: &str, desktop_file_id: &str, display_name: &str) -> bool {
+    fn words(name: &str) -> impl Iterator<Item = String> + '_ {
+        name.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+    }
 
-    let path = Path::new(dir);
-    let entries = fs::read_dir(path);
-    if entries.is_err() {
-        error!("Failed to read entries in '{}'", dir);
+    let Some(binary_word) = words(binary_name).next() else {
+        return false;
+    };
+    let id = desktop_file_id
+        .strip_suffix(".desktop")
+        .unwrap_or(desktop_file_id);
+    let id = id.rsplit('.').next().unwrap_or(id);
+    words(id)
+        .chain(words(display_name))
+        .any(|word| word == binary_word)
+}
+
+/// Entries with the same name get what tells them apart appended to it, the desktop file ID, the
+/// path of the binary or the command of a custom entry
+fn disambiguate(executables: &mut [(Option<&str>, Executable)]) {
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for (_, executable) in executables.iter() {
+        *name_counts
+            .entry(executable.get_display_text().to_string())
+            .or_default() += 1;
+    }
+
+    for (desktop_file_id, executable) in executables.iter_mut() {
+        if name_counts[executable.get_display_text()] < 2 {
+            continue;
+        }
+        let detail = match desktop_file_id {
+            Some(desktop_file_id) => desktop_file_id.to_string(),
+            None => executable
+                .path
+                .clone()
+                .unwrap_or(executable.command.clone()),
+        };
+        executable.disambiguated_name =
+            Some(format!("{} ({detail})", executable.get_display_text()));
+    }
+}
+
+fn open_lock_file(config: &Config, name: &str) -> Result<File, ()> {
+    if let Err(err) = fs::create_dir_all(&config.cache_dir) {
+        error!(
+            "Failed to create cache directory '{}': {:?}",
+            config.cache_dir, err
+        );
         return Err(());
     }
-    let entries = entries.unwrap();
+    let lock_file = config.cache_dir.clone() + name;
+    fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&lock_file)
+        .map_err(|err| error!("Failed to open lock file '{lock_file}': {:?}", err))
+}
 
-    let mut executables = Vec::with_capacity(entries.size_hint().0);
+fn lock_cache(config: &Config) -> Result<Flock<File>, ()> {
+    Flock::lock(
+        open_lock_file(config, CACHE_LOCK_FILE_NAME)?,
+        FlockArg::LockExclusive,
+    )
+    .map_err(|(_, errno)| error!("Failed to lock cache: {:?}", errno))
+}
 
+/// Held by the watcher for as long as it runs, which fails if another one is running
+pub fn lock_watcher(config: &Config) -> Result<Flock<File>, ()> {
+    Flock::lock(
+        open_lock_file(config, WATCHER_LOCK_FILE_NAME)?,
+        FlockArg::LockExclusiveNonblock,
+    )
+    .map_err(|(_, errno)| error!("Failed to lock watcher: {:?}", errno))
+}
+
+/// Whether a watcher keeps the cache up-to-date, so there is no need to rescan
+pub fn is_watcher_running(config: &Config) -> bool {
+    let Ok(file) = File::open(config.cache_dir.clone() + WATCHER_LOCK_FILE_NAME) else {
+        return false;
+    };
+    matches!(
+        Flock::lock(file, FlockArg::LockSharedNonblock),
+        Err((_, Errno::EWOULDBLOCK))
+    )
+}
+
+/// Writes a temporary file and renames it over the cache file, so the cache is never left half
+/// written
+fn write_cache_file(
+    config: &Config,
+    context: &DesktopFileContext,
+    directories: &[ScannedDirectory],
+) -> Result<(), ()> {
+    let cache_file = config.cache_dir.clone() + CACHE_FILE_NAME;
+    let temp_file = format!("{cache_file}.{}.tmp", process::id());
+
+    let cache = Cache {
+        version: CACHE_VERSION,
+        context: context.clone(),
+        directories,
+    };
+    let contents = serde_json::to_vec(&cache)
+        .map_err(|err| error!("Failed to serialize executables: {:?}", err))?;
+
+    let result = File::create(&temp_file)
+        .and_then(|mut file| {
+            file.write_all(&contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_file, &cache_file));
+    if let Err(err) = result {
+        error!("Failed to write cache file '{cache_file}': {:?}", err);
+        let _ = fs::remove_file(&temp_file);
+        return Err(());
+    }
+    Ok(())
+}
+
+/// The cached directories, if the cache is of the current version and for `context`, or for any
+/// context with `any_context`
+fn read_cache_file(
+    config: &Config,
+    context: &DesktopFileContext,
+    any_context: bool,
+) -> Result<Vec<ScannedDirectory>, ()> {
+    let contents = match fs::read(config.cache_dir.clone() + CACHE_FILE_NAME) {
+        Ok(contents) => contents,
+        Err(_) => {
+            info!("Cache file does not exist, creating a new one");
+            return Err(());
+        }
+    };
+
+    let version = serde_json::from_slice::<CacheVersion>(&contents)
+        .map_err(|err| error!("Cache file is corrupt: {:?}", err))?
+        .version;
+    if version != CACHE_VERSION {
+        info!("Cache file has version {version} instead of {CACHE_VERSION}");
+        return Err(());
+    }
+
+    let cache = serde_json::from_slice::<Cache<Vec<ScannedDirectory>>>(&contents)
+        .map_err(|err| error!("Cache file is corrupt: {:?}", err))?;
+    if cache.context != *context {
+        if !any_context {
+            info!("Cache file is for {:?}", cache.context);
+            return Err(());
+        }
+        error!(
+            "Cache file is for {:?} instead of {:?}, the watcher should run in the same environment",
+            cache.context, context
+        );
+    }
+    Ok(cache.directories)
+}
+
+fn scan_directory(
+    path: String,
+    kind: DirectoryKind,
+    context: &DesktopFileContext,
+) -> ScannedDirectory {
+    info!("Collecting from dir: {}", path);
+
+    let mut scanned = ScannedDirectory {
+        modified: get_modified_time(&path),
+        path,
+        kind,
+        desktop_files: vec![],
+        binaries: vec![],
+    };
+    let (id_prefix, include_binaries, include_desktop_files) = match &scanned.kind {
+        DirectoryKind::Applications { id_prefix } => (id_prefix.as_str(), false, true),
+        DirectoryKind::Binaries {
+            include_binaries,
+            include_desktop_files,
+        } => ("", *include_binaries, *include_desktop_files),
+    };
+
+    let entries = match fs::read_dir(&scanned.path) {
+        Ok(entries) => entries,
+        Err(_) => {
+            error!("Failed to read entries in '{}'", scanned.path);
+            return scanned;
+        }
+    };
+
+    let mut desktop_files = vec![];
     for entry in entries {
         if entry.is_err() {
             error!(
@@ -270,61 +817,391 @@ fn get_executables_from_directory(
             .map(|e| e == "desktop")
             .unwrap_or(false);
 
-        if !is_desktop_file && !include_binaries {
+        if is_desktop_file && include_desktop_files {
+            let desktop_file_id = id_prefix.to_string() + &entry.file_name().to_string_lossy();
+            desktop_files.push((desktop_file_id, entry.path()));
             continue;
-        } else if is_desktop_file && include_desktop_files {
-            let file = File::open(entry.path()).unwrap();
-            let mut name: Option<String> = None;
-            let mut exec: Option<String> = None;
-            for line_result in BufReader::new(file).lines() {
-                let entry = line_result.unwrap();
-                if entry.starts_with("Name") {
-                    name = Some(entry.split("=").nth(1).unwrap().to_string());
-                } else if entry.starts_with("Exec") {
-                    let entry = entry.split_once("=").unwrap().1;
-                    let regex = Regex::new(r"%\w").unwrap();
-                    exec = Some(regex.replace_all(entry, "").to_string());
-                }
-                if name.is_some() && exec.is_some() {
-                    break;
-                }
-            }
-            if name.is_none() || exec.is_none() {
-                error!(
-                    "Failed to get name and exec for desktop file '{}'",
-                    entry.path().to_str().unwrap()
-                );
-                continue;
-            }
+        } else if !include_binaries {
+            continue;
+        }
+
+        if (file_type.is_file() || file_type.is_symlink()) && is_executable(&entry.path()) {
+            let binary_name = entry.file_name().to_string_lossy().to_string();
+            info!("New binary: {binary_name}");
+            let binary_path = entry.path().display().to_string();
+            scanned
+                .binaries
+                .push(Executable::new_binary(binary_name, binary_path));
+        }
+    }
+
+    // Directories like /usr/share/applications hold thousands of desktop files
+    scanned.desktop_files = desktop_files
+        .into_par_iter()
+        .map(|(desktop_file_id, path)| {
+            // The ID is kept even when the desktop file can't be listed, so it still shadows
+            let desktop_executables =
+                get_executables_from_desktop_file(&path, context).unwrap_or_default();
+            (desktop_file_id, desktop_executables)
+        })
+        .collect();
+    scanned
+}
+
+/// Whether the current user can run the file at `path`, like `execvp` decides it. Symlinks are
+/// followed, so broken ones and ones to directories are not executable.
+pub fn is_executable(path: &Path) -> bool {
+    // Directories can have the execute bit too, it means they can be entered
+    if !path
+        .metadata()
+        .map(|metadata| metadata.is_file())
+        .unwrap_or(false)
+    {
+        return false;
+    }
+    // AT_EACCESS checks with the effective IDs and the supplementary groups, like `exec` does
+    faccessat(None, path, AccessFlags::X_OK, AtFlags::AT_EACCESS).is_ok()
+}
+
+/// Finds `binary` in the directories of PATH, or checks it directly if it is a path
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    if binary.contains('/') {
+        let path = PathBuf::from(binary);
+        return is_executable(&path).then_some(path);
+    }
+
+    env::var("PATH")
+        .ok()?
+        .split(':')
+        .map(|dir| Path::new(dir).join(binary))
+        .find(|path| is_executable(path))
+}
+
+/// The environment desktop files are localized and filtered for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct DesktopFileContext {
+    locale: Option<Locale>,
+    /// Names from `XDG_CURRENT_DESKTOP`, matched against `OnlyShowIn` and `NotShowIn`
+    current_desktops: Vec<String>,
+}
+
+impl DesktopFileContext {
+    fn from_env() -> DesktopFileContext {
+        DesktopFileContext {
+            locale: Locale::from_env(),
+            current_desktops: env::var("XDG_CURRENT_DESKTOP")
+                .map(|desktops| {
+                    desktops
+                        .split(':')
+                        .filter(|desktop| !desktop.is_empty())
+                        .map(|desktop| desktop.to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Whether the desktop entry asks not to be listed in the current environment
+fn should_hide_desktop_entry(desktop_entry: &DesktopEntry, context: &DesktopFileContext) -> bool {
+    let path = desktop_entry.get_path();
+
+    // Hidden means the entry was deleted, NoDisplay that it exists only for MIME handling
+    for key in ["Hidden", "NoDisplay"] {
+        if desktop_entry.get_boolean(DESKTOP_ENTRY_GROUP, key) == Some(true) {
+            info!("Skipping desktop file '{path}' with {key}=true");
+            return true;
+        }
+    }
+
+    if let Some(only_show_in) = desktop_entry.get_strings(DESKTOP_ENTRY_GROUP, "OnlyShowIn") {
+        if !context
+            .current_desktops
+            .iter()
+            .any(|desktop| only_show_in.contains(desktop))
+        {
             info!(
-                "New desktop file: {} - {}",
-                name.as_ref().unwrap(),
-                exec.as_ref().unwrap()
+                "Skipping desktop file '{path}' only shown in {:?}",
+                only_show_in
             );
-            executables.push(Executable::new_desktop_file(exec.unwrap(), name.unwrap()));
-            continue;
+            return true;
         }
+    }
+    if let Some(not_show_in) = desktop_entry.get_strings(DESKTOP_ENTRY_GROUP, "NotShowIn") {
+        if context
+            .current_desktops
+            .iter()
+            .any(|desktop| not_show_in.contains(desktop))
+        {
+            info!(
+                "Skipping desktop file '{path}' not shown in {:?}",
+                not_show_in
+            );
+            return true;
+        }
+    }
 
-        if file_type.is_file() || file_type.is_symlink() {
-            let metadata = entry.metadata();
-            if metadata.is_err() {
-                error!("Failed to read metadata of entry '{:?}'", entry);
-                continue;
-            }
+    if let Some(try_exec) = desktop_entry.get_string(DESKTOP_ENTRY_GROUP, "TryExec") {
+        if find_in_path(&try_exec).is_none() {
+            info!("Skipping desktop file '{path}' since '{try_exec}' is not installed");
+            return true;
+        }
+    }
 
-            let metadata = metadata.unwrap();
-            let permissions = metadata.permissions();
-            if permissions.mode() & 0o100 != 0 {
-                let binary_name = Path::new(&entry.path().display().to_string())
-                    .file_name()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_string();
-                info!("New binary: {binary_name}");
-                executables.push(Executable::new_binary(binary_name));
-            }
+    false
+}
+
+/// The application of a desktop file, followed by its actions like "Firefox: New Private Window"
+fn get_executables_from_desktop_file(
+    path: &Path,
+    context: &DesktopFileContext,
+) -> Result<Vec<Executable>, ()> {
+    let desktop_entry = DesktopEntry::parse_file(path)?;
+
+    // Links and directories can't be launched
+    let entry_type = desktop_entry.get_string(DESKTOP_ENTRY_GROUP, "Type");
+    if entry_type.as_deref() != Some("Application") {
+        info!(
+            "Skipping desktop file '{}' of type {:?}",
+            path.display(),
+            entry_type
+        );
+        return Err(());
+    }
+
+    if should_hide_desktop_entry(&desktop_entry, context) {
+        return Err(());
+    }
+
+    let locale = context.locale.as_ref();
+    let Some(name) = desktop_entry.get_locale_string(DESKTOP_ENTRY_GROUP, "Name", locale) else {
+        error!("Failed to get name for desktop file '{}'", path.display());
+        return Err(());
+    };
+
+    // Actions share how the application is launched
+    let terminal = desktop_entry
+        .get_boolean(DESKTOP_ENTRY_GROUP, "Terminal")
+        .unwrap_or(false);
+    let working_dir = desktop_entry
+        .get_string(DESKTOP_ENTRY_GROUP, "Path")
+        .filter(|dir| !dir.is_empty());
+    let prefers_non_default_gpu = desktop_entry
+        .get_boolean(DESKTOP_ENTRY_GROUP, "PrefersNonDefaultGPU")
+        .unwrap_or(false);
+    let keywords = desktop_entry
+        .get_locale_strings(DESKTOP_ENTRY_GROUP, "Keywords", locale)
+        .unwrap_or_default();
+    let generic_name = desktop_entry.get_locale_string(DESKTOP_ENTRY_GROUP, "GenericName", locale);
+    let comment = desktop_entry.get_locale_string(DESKTOP_ENTRY_GROUP, "Comment", locale);
+    let icon = desktop_entry
+        .get_locale_string(DESKTOP_ENTRY_GROUP, "Icon", locale)
+        .filter(|icon| !icon.is_empty());
+    let new_executable = |command, display_name| Executable {
+        terminal,
+        working_dir: working_dir.clone(),
+        prefers_non_default_gpu,
+        keywords: keywords.clone(),
+        generic_name: generic_name.clone(),
+        comment: comment.clone(),
+        icon: icon.clone(),
+        ..Executable::new_desktop_file(command, display_name)
+    };
+
+    let mut executables = Vec::new();
+    // Applications that are only activated over D-Bus can still have actions with an Exec
+    if let Ok(args) = desktop_entry.get_exec_args(DESKTOP_ENTRY_GROUP, locale) {
+        let command = join_exec_args(&args);
+        info!("New desktop file: {} - {}", name, command);
+        executables.push(new_executable(command, name.clone()));
+    }
+
+    for action in desktop_entry
+        .get_strings(DESKTOP_ENTRY_GROUP, "Actions")
+        .unwrap_or_default()
+    {
+        let group = format!("Desktop Action {action}");
+        if !desktop_entry.has_group(&group) {
+            error!("{}: Missing group for action '{action}'", path.display());
+            continue;
         }
+        let Some(action_name) = desktop_entry.get_locale_string(&group, "Name", locale) else {
+            error!("{}: Missing name for action '{action}'", path.display());
+            continue;
+        };
+        let Ok(args) = desktop_entry.get_exec_args(&group, locale) else {
+            continue;
+        };
+
+        let display_name = format!("{name}: {action_name}");
+        let command = join_exec_args(&args);
+        info!("New desktop action: {} - {}", display_name, command);
+        let mut executable = new_executable(command, display_name);
+        executable.is_action = true;
+        // Actions can have an icon of their own
+        if let Some(action_icon) = desktop_entry
+            .get_locale_string(&group, "Icon", locale)
+            .filter(|icon| !icon.is_empty())
+        {
+            executable.icon = Some(action_icon);
+        }
+        executables.push(executable);
+    }
+
+    if executables.is_empty() {
+        return Err(());
     }
     Ok(executables)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary(name: &str) -> Executable {
+        Executable::new_binary(name.to_string(), format!("/usr/bin/{name}"))
+    }
+
+    fn desktop_entry(id: &'static str, name: &str, exec: &str) -> (&'static str, Executable) {
+        (
+            id,
+            Executable::new_desktop_file(exec.to_string(), name.to_string()),
+        )
+    }
+
+    /// Links `desktop_entries` to `binaries` and returns the names of the linked binaries
+    fn link(
+        desktop_entries: &mut [(&str, Executable)],
+        binaries: &[Executable],
+    ) -> HashSet<String> {
+        let binary_indexes: HashMap<&str, usize> = binaries
+            .iter()
+            .enumerate()
+            .map(|(index, binary)| (binary.command.as_str(), index))
+            .collect();
+        link_binaries(desktop_entries, binaries, &binary_indexes)
+            .into_iter()
+            .map(|index| binaries[index].command.clone())
+            .collect()
+    }
+
+    #[test]
+    fn matches_binary_names_to_applications() {
+        assert!(names_match(
+            "gimp-2.10",
+            "gimp.desktop",
+            "GNU Image Manipulation Program"
+        ));
+        assert!(names_match(
+            "nautilus",
+            "org.gnome.Nautilus.desktop",
+            "Files"
+        ));
+        assert!(names_match("firefox", "firefox-esr.desktop", "Firefox ESR"));
+        assert!(names_match("code", "code.desktop", "Visual Studio Code"));
+        assert!(!names_match("sh", "org.example.Tool.desktop", "Tool"));
+        assert!(!names_match(
+            "flatpak",
+            "org.gnome.Nautilus.desktop",
+            "Files"
+        ));
+        assert!(!names_match("--", "gimp.desktop", "GIMP"));
+    }
+
+    #[test]
+    fn links_binaries_to_the_applications_they_run() {
+        let binaries = [binary("nautilus"), binary("sh"), binary("flatpak")];
+        let mut desktop_entries = [
+            desktop_entry(
+                "org.gnome.Nautilus.desktop",
+                "Files",
+                "nautilus --new-window",
+            ),
+            desktop_entry("tool.desktop", "Tool", "sh -c tool"),
+            desktop_entry(
+                "org.gnome.Maps.desktop",
+                "Maps",
+                "env GDK_BACKEND=x11 flatpak run org.gnome.Maps",
+            ),
+        ];
+        let linked = link(&mut desktop_entries, &binaries);
+        assert_eq!(linked, HashSet::from(["nautilus".to_string()]));
+
+        let files = &desktop_entries[0].1;
+        assert_eq!(files.path.as_deref(), Some("/usr/bin/nautilus"));
+        assert_eq!(
+            files.get_launch_targets().collect::<Vec<_>>(),
+            ["nautilus --new-window", "/usr/bin/nautilus"]
+        );
+        assert!(files
+            .get_secondary_search_texts()
+            .any(|text| text == "nautilus"));
+        assert_eq!(desktop_entries[1].1.path, None);
+        assert_eq!(desktop_entries[2].1.path, None);
+    }
+
+    #[test]
+    fn links_no_binary_that_several_applications_run() {
+        let binaries = [binary("libreoffice")];
+        let mut desktop_entries = [
+            desktop_entry(
+                "libreoffice-writer.desktop",
+                "LibreOffice Writer",
+                "libreoffice --writer",
+            ),
+            desktop_entry(
+                "libreoffice-calc.desktop",
+                "LibreOffice Calc",
+                "libreoffice --calc",
+            ),
+        ];
+        assert!(link(&mut desktop_entries, &binaries).is_empty());
+    }
+
+    #[test]
+    fn links_no_binary_to_actions_or_other_paths() {
+        let binaries = [binary("firefox")];
+        let (id, mut action) = desktop_entry(
+            "firefox.desktop",
+            "New Private Window",
+            "firefox --private-window",
+        );
+        action.is_action = true;
+        let mut desktop_entries = [
+            (id, action),
+            desktop_entry(
+                "firefox.desktop",
+                "Firefox",
+                "/nonexistent/firefox/bin/firefox %u",
+            ),
+        ];
+        assert!(link(&mut desktop_entries, &binaries).is_empty());
+    }
+
+    #[test]
+    fn disambiguates_equal_names() {
+        let mut executables: Vec<(Option<&str>, Executable)> = [
+            desktop_entry("xterm.desktop", "Terminal", "xterm"),
+            desktop_entry("org.gnome.Terminal.desktop", "Terminal", "gnome-terminal"),
+            desktop_entry("firefox.desktop", "Firefox", "firefox"),
+        ]
+        .into_iter()
+        .map(|(id, executable)| (Some(id), executable))
+        .chain([(None, binary("Terminal"))])
+        .collect();
+        disambiguate(&mut executables);
+        let names: Vec<&str> = executables
+            .iter()
+            .map(|(_, executable)| executable.get_display_text())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "Terminal (xterm.desktop)",
+                "Terminal (org.gnome.Terminal.desktop)",
+                "Firefox",
+                "Terminal (/usr/bin/Terminal)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+pub static HISTORY_FILE_NAME: &str = "/history.json";
+pub static HISTORY_LOCK_FILE_NAME: &str = "/history.lock";
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info};
+use nix::fcntl::{Flock, FlockArg};
+use serde::{Deserialize, Serialize};
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryEntry {
+    count: u32,
+    /// Seconds since the unix epoch
+    last_used: u64,
+}
+
+/// Launch counts and times, persisted in the cache directory
+#[derive(Debug, Default)]
+pub struct History {
+    cache_dir: String,
+    entries: HashMap<String, HistoryEntry>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_entries(file: &str) -> Result<HashMap<String, HistoryEntry>, ()> {
+    let contents = fs::read_to_string(file).map_err(|_| {
+        info!("History file '{file}' does not exist yet");
+    })?;
+    serde_json::from_str(&contents).map_err(|err| {
+        error!("History file '{file}' is corrupt: {:?}", err);
+    })
+}
+
+impl History {
+    pub fn load(cache_dir: &str) -> History {
+        History {
+            cache_dir: cache_dir.to_string(),
+            entries: read_entries(&(cache_dir.to_string() + HISTORY_FILE_NAME)).unwrap_or_default(),
+        }
+    }
+
+    /// Held while the history file is read and written again, so launches recorded by other
+    /// instances in between are not lost
+    fn lock(&self) -> Result<Flock<File>, ()> {
+        if let Err(err) = fs::create_dir_all(&self.cache_dir) {
+            error!(
+                "Failed to create cache directory '{}': {:?}",
+                self.cache_dir, err
+            );
+            return Err(());
+        }
+        let lock_file = self.cache_dir.clone() + HISTORY_LOCK_FILE_NAME;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&lock_file)
+            .map_err(|err| error!("Failed to open lock file '{lock_file}': {:?}", err))?;
+        Flock::lock(file, FlockArg::LockExclusive)
+            .map_err(|(_, errno)| error!("Failed to lock history: {:?}", errno))
+    }
+
+    /// Writes a temporary file and renames it over the history file, so the history is never
+    /// left half written
+    fn save(&self) -> Result<(), ()> {
+        let file = self.cache_dir.clone() + HISTORY_FILE_NAME;
+        let temp_file = format!("{file}.{}.tmp", process::id());
+
+        let contents = serde_json::to_vec(&self.entries)
+            .map_err(|err| error!("Failed to serialize history: {:?}", err))?;
+
+        let result = File::create(&temp_file)
+            .and_then(|mut file| {
+                file.write_all(&contents)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp_file, &file));
+        if let Err(err) = result {
+            error!("Failed to write history file '{file}': {:?}", err);
+            let _ = fs::remove_file(&temp_file);
+            return Err(());
+        }
+        Ok(())
+    }
+
+    pub fn record_launch(&mut self, key: &str) {
+        let _lock = self.lock();
+        // Other instances, like a one-shot launcher next to the daemon, may have recorded
+        // launches since the history was loaded
+        if let Ok(entries) = read_entries(&(self.cache_dir.clone() + HISTORY_FILE_NAME)) {
+            self.entries = entries;
+        }
+
+        let entry = self.entries.entry(key.to_string()).or_insert(HistoryEntry {
+            count: 0,
+            last_used: 0,
+        });
+        entry.count += 1;
+        entry.last_used = now();
+        info!("Launched '{key}' {} times", entry.count);
+        if self.save().is_err() {
+            error!("Failed to save history");
+        }
+    }
+
+    /// Launch count weighted by how recently it was last launched, 0 if it never was
+    pub fn get_frecency(&self, key: &str) -> f64 {
+        let Some(entry) = self.entries.get(key) else {
+            return 0.0;
+        };
+
+        let age = now().saturating_sub(entry.last_used);
+        let weight = if age < 4 * HOUR {
+            100.0
+        } else if age < DAY {
+            80.0
+        } else if age < 7 * DAY {
+            60.0
+        } else if age < 30 * DAY {
+            40.0
+        } else if age < 90 * DAY {
+            20.0
+        } else {
+            10.0
+        };
+        entry.count as f64 * weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn keeps_launches_of_other_instances() {
+        let cache_dir = format!(
+            "{}/menuvroom-history-test-{}",
+            env::temp_dir().display(),
+            process::id()
+        );
+        let _ = fs::remove_dir_all(&cache_dir);
+
+        let mut daemon = History::load(&cache_dir);
+        let mut one_shot = History::load(&cache_dir);
+        one_shot.record_launch("firefox");
+        daemon.record_launch("gimp");
+        daemon.record_launch("firefox");
+
+        let history = History::load(&cache_dir);
+        assert_eq!(history.entries["firefox"].count, 2);
+        assert_eq!(history.entries["gimp"].count, 1);
+        assert!(history.get_frecency("firefox") > history.get_frecency("gimp"));
+        assert_eq!(history.get_frecency("vim"), 0.0);
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
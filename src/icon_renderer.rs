@@ -0,0 +1,288 @@
+static SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0) var atlas: texture_2d<f32>;
+@group(0) @binding(1) var atlas_sampler: sampler;
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) uv: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(atlas, atlas_sampler, in.uv);
+}
+"#;
+
+/// Two floats for the position and two for the texture coordinates
+const VERTEX_SIZE: u64 = 4 * 4;
+const VERTICES_PER_ICON: u64 = 6;
+/// The atlas is a square grid of icon slots
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_SLOTS: usize = (ATLAS_COLUMNS * ATLAS_COLUMNS) as usize;
+
+use std::collections::HashMap;
+
+/// Icon to draw at a position in physical pixels
+pub struct Icon<'a> {
+    pub name: &'a str,
+    pub left: f32,
+    pub top: f32,
+}
+
+/// Draws square icons of a fixed size from a texture atlas. When the atlas is full, the icon
+/// that was added first makes room for the new one.
+pub struct IconRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    atlas_texture: wgpu::Texture,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+    icon_size: u32,
+    slots: HashMap<String, usize>,
+    /// Names of the icons in each slot
+    slot_names: Vec<Option<String>>,
+    next_slot: usize,
+}
+
+impl IconRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, icon_size: u32) -> Self {
+        let atlas_size = icon_size * ATLAS_COLUMNS;
+        let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("icon atlas"),
+            size: wgpu::Extent3d {
+                width: atlas_size,
+                height: atlas_size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let atlas_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("icon atlas sampler"),
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("icon bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("icon bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&atlas_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("icon shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("icon pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("icon pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: VERTEX_SIZE,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // Rasterized icons have premultiplied alpha
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+            atlas_texture,
+            vertex_buffer: Self::create_vertex_buffer(device, 1),
+            vertex_count: 0,
+            icon_size,
+            slots: HashMap::new(),
+            slot_names: vec![None; ATLAS_SLOTS],
+            next_slot: 0,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, icon_capacity: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("icon vertex buffer"),
+            size: icon_capacity * VERTICES_PER_ICON * VERTEX_SIZE,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn get_icon_size(&self) -> u32 {
+        self.icon_size
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.slots.contains_key(name)
+    }
+
+    /// Adds the premultiplied RGBA `pixels` of a square icon of the renderer's size
+    pub fn insert(&mut self, queue: &wgpu::Queue, name: &str, pixels: &[u8]) {
+        let slot = self.next_slot;
+        self.next_slot = (self.next_slot + 1) % ATLAS_SLOTS;
+        if let Some(evicted) = self.slot_names[slot].replace(name.to_string()) {
+            self.slots.remove(&evicted);
+        }
+        self.slots.insert(name.to_string(), slot);
+
+        let (x, y) = Self::get_slot_origin(slot);
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.atlas_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: x * self.icon_size,
+                    y: y * self.icon_size,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * self.icon_size),
+                rows_per_image: Some(self.icon_size),
+            },
+            wgpu::Extent3d {
+                width: self.icon_size,
+                height: self.icon_size,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Column and row of `slot` in the atlas
+    fn get_slot_origin(slot: usize) -> (u32, u32) {
+        (slot as u32 % ATLAS_COLUMNS, slot as u32 / ATLAS_COLUMNS)
+    }
+
+    /// Uploads the icons to draw in the next `render` call. Icons that were never inserted are
+    /// skipped.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resolution: (u32, u32),
+        icons: &[Icon],
+    ) {
+        let width = resolution.0 as f32;
+        let height = resolution.1 as f32;
+        let size = self.icon_size as f32;
+
+        let mut vertices: Vec<u8> =
+            Vec::with_capacity(icons.len() * (VERTICES_PER_ICON * VERTEX_SIZE) as usize);
+        let mut icon_count = 0;
+        for icon in icons {
+            let Some(slot) = self.slots.get(icon.name) else {
+                continue;
+            };
+            icon_count += 1;
+
+            // Pixel coordinates to normalized device coordinates
+            let left = icon.left / width * 2.0 - 1.0;
+            let right = (icon.left + size) / width * 2.0 - 1.0;
+            let top = 1.0 - icon.top / height * 2.0;
+            let bottom = 1.0 - (icon.top + size) / height * 2.0;
+
+            let (column, row) = Self::get_slot_origin(*slot);
+            let uv_left = column as f32 / ATLAS_COLUMNS as f32;
+            let uv_right = (column + 1) as f32 / ATLAS_COLUMNS as f32;
+            let uv_top = row as f32 / ATLAS_COLUMNS as f32;
+            let uv_bottom = (row + 1) as f32 / ATLAS_COLUMNS as f32;
+
+            for vertex in [
+                [left, top, uv_left, uv_top],
+                [left, bottom, uv_left, uv_bottom],
+                [right, bottom, uv_right, uv_bottom],
+                [left, top, uv_left, uv_top],
+                [right, bottom, uv_right, uv_bottom],
+                [right, top, uv_right, uv_top],
+            ] {
+                for value in vertex {
+                    vertices.extend_from_slice(&value.to_ne_bytes());
+                }
+            }
+        }
+
+        if vertices.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = Self::create_vertex_buffer(device, icon_count);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, &vertices);
+        self.vertex_count = (icon_count * VERTICES_PER_ICON) as u32;
+    }
+
+    pub fn render<'pass>(&'pass self, pass: &mut wgpu::RenderPass<'pass>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+}
@@ -0,0 +1,422 @@
+static FALLBACK_ICON_THEME: &str = "hicolor";
+static INDEX_THEME_GROUP: &str = "Icon Theme";
+static GTK_SETTINGS_SUB_PATH: &str = "/.config/gtk-3.0/settings.ini";
+/// Only formats that can be rasterized, so a PNG of another size wins over an XPM
+static ICON_EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{error, info};
+use resvg::{tiny_skia, usvg};
+
+use crate::desktop_entry::DesktopEntry;
+use crate::executables;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirectoryType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// A subdirectory of an icon theme and the icon sizes it holds
+#[derive(Debug)]
+struct IconDirectory {
+    name: String,
+    size: u32,
+    scale: u32,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+    directory_type: DirectoryType,
+}
+
+impl IconDirectory {
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.directory_type {
+            DirectoryType::Fixed => self.size == size,
+            DirectoryType::Scalable => self.min_size <= size && size <= self.max_size,
+            DirectoryType::Threshold => {
+                self.size.saturating_sub(self.threshold) <= size
+                    && size <= self.size + self.threshold
+            }
+        }
+    }
+
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let (min_size, max_size) = match self.directory_type {
+            DirectoryType::Fixed => (self.size, self.size),
+            DirectoryType::Scalable => (self.min_size, self.max_size),
+            DirectoryType::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        let scaled_size = size * scale;
+        if scaled_size < min_size * self.scale {
+            min_size * self.scale - scaled_size
+        } else {
+            scaled_size.saturating_sub(max_size * self.scale)
+        }
+    }
+}
+
+/// Names of the files in icon directories. Each directory is listed once, since looking up a
+/// missing icon would check hundreds of paths otherwise.
+#[derive(Debug, Default)]
+struct DirectoryListings {
+    file_names: HashMap<PathBuf, HashSet<OsString>>,
+}
+
+impl DirectoryListings {
+    fn contains(&mut self, path: &Path) -> bool {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        self.file_names
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                fs::read_dir(dir)
+                    .map(|entries| entries.flatten().map(|entry| entry.file_name()).collect())
+                    .unwrap_or_default()
+            })
+            .contains(name)
+    }
+}
+
+/// An icon theme from its `index.theme`, see
+/// https://specifications.freedesktop.org/icon-theme-spec/latest/
+#[derive(Debug)]
+struct IconTheme {
+    /// Base directories that have a directory for the theme
+    dirs: Vec<PathBuf>,
+    directories: Vec<IconDirectory>,
+    parents: Vec<String>,
+}
+
+impl IconTheme {
+    fn load(name: &str, base_dirs: &[PathBuf]) -> Option<IconTheme> {
+        let dirs: Vec<PathBuf> = base_dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        // The first index.theme found describes the theme
+        let index = dirs
+            .iter()
+            .map(|dir| dir.join("index.theme"))
+            .find(|index| index.is_file())?;
+        let index = DesktopEntry::parse_file(&index).ok()?;
+
+        let get_size = |group: &str, key: &str| {
+            index
+                .get_numeric(group, key)
+                .filter(|size| *size >= 0.0)
+                .map(|size| size as u32)
+        };
+
+        // Unlike desktop files, index.theme separates lists with commas
+        let get_list = |key: &str| -> Vec<String> {
+            index
+                .get_string(INDEX_THEME_GROUP, key)
+                .unwrap_or_default()
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        };
+
+        let mut directories = Vec::new();
+        for directory in [get_list("Directories"), get_list("ScaledDirectories")].concat() {
+            let Some(size) = get_size(&directory, "Size") else {
+                error!("{}: Missing size for '{directory}'", index.get_path());
+                continue;
+            };
+            let directory_type = match index.get_string(&directory, "Type").as_deref() {
+                Some("Fixed") => DirectoryType::Fixed,
+                Some("Scalable") => DirectoryType::Scalable,
+                _ => DirectoryType::Threshold,
+            };
+            directories.push(IconDirectory {
+                size,
+                scale: get_size(&directory, "Scale").unwrap_or(1).max(1),
+                min_size: get_size(&directory, "MinSize").unwrap_or(size),
+                max_size: get_size(&directory, "MaxSize").unwrap_or(size),
+                threshold: get_size(&directory, "Threshold").unwrap_or(2),
+                directory_type,
+                name: directory,
+            });
+        }
+
+        Some(IconTheme {
+            dirs,
+            directories,
+            parents: get_list("Inherits"),
+        })
+    }
+
+    fn lookup(
+        &self,
+        icon: &str,
+        size: u32,
+        scale: u32,
+        listings: &mut DirectoryListings,
+    ) -> Option<PathBuf> {
+        let candidates = || {
+            self.directories.iter().flat_map(move |directory| {
+                self.dirs.iter().flat_map(move |dir| {
+                    ICON_EXTENSIONS.iter().map(move |extension| {
+                        (
+                            directory,
+                            dir.join(&directory.name)
+                                .join(format!("{icon}.{extension}")),
+                        )
+                    })
+                })
+            })
+        };
+
+        if let Some((_, path)) = candidates().find(|(directory, path)| {
+            directory.matches_size(size, scale) && listings.contains(path)
+        }) {
+            return Some(path);
+        }
+        candidates()
+            .filter(|(_, path)| listings.contains(path))
+            .min_by_key(|(directory, _)| directory.size_distance(size, scale))
+            .map(|(_, path)| path)
+    }
+}
+
+/// Icon theme from the GTK settings, since most desktops keep it in sync with their own
+fn get_gtk_icon_theme() -> Option<String> {
+    let settings = env::var("HOME").ok()? + GTK_SETTINGS_SUB_PATH;
+    if !Path::new(&settings).is_file() {
+        return None;
+    }
+    DesktopEntry::parse_file(Path::new(&settings))
+        .ok()?
+        .get_string("Settings", "gtk-icon-theme-name")
+}
+
+/// Finds icons by name and rasterizes them to a fixed size
+pub struct IconLoader {
+    base_dirs: Vec<PathBuf>,
+    /// The configured theme and everything it inherits, in lookup order
+    themes: Vec<IconTheme>,
+    /// Size of the rasterized icons in physical pixels
+    size: u32,
+    /// Scale factor of the window, which picks the directories for HiDPI screens
+    scale: u32,
+    listings: DirectoryListings,
+    /// Icons that could not be found or read, so they are not looked up again
+    failed: HashSet<String>,
+}
+
+impl IconLoader {
+    pub fn new(theme: Option<&str>, size: u32, scale: u32) -> IconLoader {
+        let mut base_dirs: Vec<PathBuf> = env::var("HOME")
+            .ok()
+            .map(|home| PathBuf::from(home + "/.icons"))
+            .into_iter()
+            .chain(
+                executables::get_data_dirs()
+                    .into_iter()
+                    .map(|dir| PathBuf::from(dir + "/icons")),
+            )
+            .chain([PathBuf::from("/usr/share/pixmaps")])
+            .filter(|dir| dir.is_dir())
+            .collect();
+        base_dirs.dedup();
+
+        let theme = theme
+            .map(|theme| theme.to_string())
+            .or_else(get_gtk_icon_theme)
+            .unwrap_or(FALLBACK_ICON_THEME.to_string());
+        info!("Using icon theme '{theme}'");
+
+        // Parents are searched depth first, and hicolor always comes last
+        let mut themes: Vec<IconTheme> = Vec::new();
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut pending = vec![FALLBACK_ICON_THEME.to_string(), theme];
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let Some(theme) = IconTheme::load(&name, &base_dirs) else {
+                info!("Icon theme '{name}' is not installed");
+                continue;
+            };
+            pending.extend(
+                theme
+                    .parents
+                    .iter()
+                    .rev()
+                    .filter(|parent| *parent != FALLBACK_ICON_THEME)
+                    .cloned(),
+            );
+            themes.push(theme);
+        }
+
+        IconLoader {
+            base_dirs,
+            themes,
+            size,
+            scale: scale.max(1),
+            listings: DirectoryListings::default(),
+            failed: HashSet::new(),
+        }
+    }
+
+    fn find(&mut self, icon: &str) -> Option<PathBuf> {
+        if icon.starts_with('/') {
+            return Some(PathBuf::from(icon));
+        }
+        // Some desktop files name the file instead of the icon
+        let icon = ICON_EXTENSIONS
+            .iter()
+            .find_map(|extension| icon.strip_suffix(&format!(".{extension}")))
+            .unwrap_or(icon);
+
+        // Themes are looked up by the size in logical pixels
+        let size = (self.size / self.scale).max(1);
+        self.themes
+            .iter()
+            .find_map(|theme| theme.lookup(icon, size, self.scale, &mut self.listings))
+            .or_else(|| {
+                self.base_dirs.iter().find_map(|dir| {
+                    ICON_EXTENSIONS
+                        .iter()
+                        .map(|extension| dir.join(format!("{icon}.{extension}")))
+                        .find(|path| self.listings.contains(path))
+                })
+            })
+    }
+
+    /// Premultiplied RGBA pixels of `icon`, scaled to fit a square of the loader's size
+    pub fn load(&mut self, icon: &str) -> Option<Vec<u8>> {
+        if self.failed.contains(icon) {
+            return None;
+        }
+        let pixels = self.find(icon).and_then(|path| rasterize(&path, self.size));
+        if pixels.is_none() {
+            info!("No usable icon found for '{icon}'");
+            self.failed.insert(icon.to_string());
+        }
+        pixels
+    }
+}
+
+fn rasterize(path: &Path, size: u32) -> Option<Vec<u8>> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Failed to read icon '{}': {:?}", path.display(), err);
+            return None;
+        }
+    };
+    let mut pixmap = tiny_skia::Pixmap::new(size, size)?;
+
+    if path.extension().is_some_and(|extension| extension == "svg") {
+        let tree = match usvg::Tree::from_data(&data, &usvg::Options::default()) {
+            Ok(tree) => tree,
+            Err(err) => {
+                error!("Failed to parse icon '{}': {:?}", path.display(), err);
+                return None;
+            }
+        };
+        let tree_size = tree.size();
+        let scale = size as f32 / tree_size.width().max(tree_size.height());
+        let transform = centered(scale, tree_size.width(), tree_size.height(), size);
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+    } else {
+        let image = match tiny_skia::Pixmap::decode_png(&data) {
+            Ok(image) => image,
+            Err(err) => {
+                error!("Failed to decode icon '{}': {:?}", path.display(), err);
+                return None;
+            }
+        };
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let scale = size as f32 / width.max(height);
+        pixmap.draw_pixmap(
+            0,
+            0,
+            image.as_ref(),
+            &tiny_skia::PixmapPaint {
+                quality: tiny_skia::FilterQuality::Bicubic,
+                ..Default::default()
+            },
+            centered(scale, width, height, size),
+            None,
+        );
+    }
+
+    Some(pixmap.take())
+}
+
+/// Scales an image of `width` by `height` and centers it in a square of `size`
+fn centered(scale: f32, width: f32, height: f32, size: u32) -> tiny_skia::Transform {
+    tiny_skia::Transform::from_scale(scale, scale).post_translate(
+        (size as f32 - width * scale) / 2.0,
+        (size as f32 - height * scale) / 2.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory(size: u32, scale: u32, directory_type: DirectoryType) -> IconDirectory {
+        IconDirectory {
+            name: format!("{size}x{size}@{scale}"),
+            size,
+            scale,
+            min_size: 16,
+            max_size: 256,
+            threshold: 2,
+            directory_type,
+        }
+    }
+
+    #[test]
+    fn matches_sizes_at_the_same_scale() {
+        let fixed = directory(24, 1, DirectoryType::Fixed);
+        assert!(fixed.matches_size(24, 1));
+        assert!(!fixed.matches_size(25, 1));
+        assert!(!fixed.matches_size(24, 2));
+
+        let scaled = directory(24, 2, DirectoryType::Fixed);
+        assert!(scaled.matches_size(24, 2));
+        assert!(!scaled.matches_size(48, 1));
+
+        let threshold = directory(24, 1, DirectoryType::Threshold);
+        assert!(threshold.matches_size(22, 1));
+        assert!(threshold.matches_size(26, 1));
+        assert!(!threshold.matches_size(27, 1));
+
+        let scalable = directory(48, 1, DirectoryType::Scalable);
+        assert!(scalable.matches_size(16, 1));
+        assert!(scalable.matches_size(256, 1));
+        assert!(!scalable.matches_size(512, 1));
+    }
+
+    #[test]
+    fn measures_distance_in_physical_pixels() {
+        let small = directory(24, 1, DirectoryType::Fixed);
+        let large = directory(48, 1, DirectoryType::Fixed);
+        // 24 logical pixels at scale 2 are best served by a 48 pixel icon
+        assert_eq!(large.size_distance(24, 2), 0);
+        assert_eq!(small.size_distance(24, 2), 24);
+        assert_eq!(small.size_distance(32, 1), 8);
+    }
+}
@@ -1,11 +1,232 @@
 mod app;
 mod config;
+mod daemon;
+mod desktop_entry;
 mod executables;
+mod history;
+mod icon_renderer;
+mod icons;
+mod matcher;
+mod patterns;
+mod rect_renderer;
+mod watcher;
 
-use app::app_main;
+use std::{env, path::Path, process};
+
+use app::{app_main, Mode};
+use config::{parse_hex_color, srgb_to_wgpu_color, Config};
+use log::info;
+
+static USAGE: &str =
+    "usage: menuvroom [--dmenu | --watch | --daemon | --toggle] [-bi] [-l lines] [-p prompt] [-fn font] [-m monitor]
+                 [-nb color] [-nf color] [-sb color] [-sf color] [-v]";
+
+/// Command line arguments. The flags are the same as dmenu's so scripts written for it can use
+/// menuvroom as is.
+#[derive(Debug, Default)]
+struct Args {
+    dmenu: bool,
+    /// Keep the cache up-to-date instead of showing a window
+    watch: bool,
+    /// Stay in the background with the window hidden until a client toggles it
+    daemon: bool,
+    /// Toggle the window of the running daemon, or open one normally when there is none
+    toggle: bool,
+    prompt: Option<String>,
+    lines: Option<usize>,
+    case_insensitive: bool,
+    font_family: Option<String>,
+    font_size: Option<f32>,
+    normal_bg: Option<[u8; 3]>,
+    normal_fg: Option<[u8; 3]>,
+    selected_bg: Option<[u8; 3]>,
+    selected_fg: Option<[u8; 3]>,
+    bottom: bool,
+    monitor: Option<usize>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+
+        // Being called through a `dmenu` symlink makes menuvroom a drop-in replacement
+        let program = args.next().unwrap_or_default();
+        parsed.dmenu = Path::new(&program)
+            .file_name()
+            .map(|name| name == "dmenu")
+            .unwrap_or(false);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dmenu" => parsed.dmenu = true,
+                "--watch" => parsed.watch = true,
+                "--daemon" => parsed.daemon = true,
+                "--toggle" => parsed.toggle = true,
+                "-b" => parsed.bottom = true,
+                "-i" => parsed.case_insensitive = true,
+                "-v" => {
+                    println!("menuvroom-{}", env!("CARGO_PKG_VERSION"));
+                    process::exit(0);
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    process::exit(0);
+                }
+                "-p" | "-l" | "-m" | "-fn" | "-nb" | "-nf" | "-sb" | "-sf" => {
+                    let Some(value) = args.next() else {
+                        return Err(format!("Missing value for '{arg}'"));
+                    };
+                    parsed.parse_option(&arg, value)?;
+                }
+                _ => return Err(format!("Unknown argument '{arg}'")),
+            }
+        }
+
+        if parsed.dmenu && (parsed.daemon || parsed.toggle) {
+            return Err("dmenu mode can't run as a daemon".to_string());
+        }
+        Ok(parsed)
+    }
+
+    fn parse_option(&mut self, flag: &str, value: String) -> Result<(), String> {
+        let parse_color = |value: &str| {
+            parse_hex_color(value).ok_or_else(|| format!("Invalid color '{value}' for '{flag}'"))
+        };
+
+        match flag {
+            "-p" => self.prompt = Some(value),
+            "-l" => {
+                self.lines = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid number of lines '{value}'"))?,
+                )
+            }
+            "-m" => {
+                self.monitor = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid monitor '{value}'"))?,
+                )
+            }
+            "-fn" => self.parse_font(&value),
+            "-nb" => self.normal_bg = Some(parse_color(&value)?),
+            "-nf" => self.normal_fg = Some(parse_color(&value)?),
+            "-sb" => self.selected_bg = Some(parse_color(&value)?),
+            "-sf" => self.selected_fg = Some(parse_color(&value)?),
+            _ => unreachable!(),
+        };
+        Ok(())
+    }
+
+    /// Reads the family and size from an Xft font pattern like `monospace:size=10` or
+    /// `DejaVu Sans Mono-12`
+    fn parse_font(&mut self, font: &str) {
+        // Xft sizes are in points, the renderer works in pixels
+        fn points_to_pixels(points: f32) -> f32 {
+            points * 96.0 / 72.0
+        }
+
+        let mut parts = font.split(':');
+        let mut family = parts.next().unwrap_or_default();
+        if let Some((name, size)) = family.rsplit_once('-') {
+            if let Ok(size) = size.parse::<f32>() {
+                family = name;
+                self.font_size = Some(points_to_pixels(size));
+            }
+        }
+        if !family.is_empty() {
+            self.font_family = Some(family.to_string());
+        }
+
+        for attribute in parts {
+            match attribute.split_once('=') {
+                Some(("size", size)) => {
+                    if let Ok(size) = size.parse::<f32>() {
+                        self.font_size = Some(points_to_pixels(size));
+                    }
+                }
+                Some(("pixelsize", size)) => {
+                    if let Ok(size) = size.parse::<f32>() {
+                        self.font_size = Some(size);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Overrides the values loaded from the config file with the ones given on the command line
+    fn apply_to_config(&self, config: &mut Config) {
+        if self.prompt.is_some() {
+            config.prompt = self.prompt.clone();
+        }
+        if self.lines.is_some() {
+            config.lines = self.lines;
+        }
+        if self.case_insensitive {
+            config.case_insensitive = true;
+        }
+        if self.font_family.is_some() {
+            config.font_family = self.font_family.clone();
+        }
+        if let Some(font_size) = self.font_size {
+            // Keep the spacing between lines proportional to the new size
+            config.line_height *= font_size / config.font_size;
+            config.font_size = font_size;
+        }
+        if let Some(rgb) = self.normal_bg {
+            config.bg_color = srgb_to_wgpu_color(rgb, config.bg_color.a);
+        }
+        if let Some([r, g, b]) = self.normal_fg {
+            config.font_color = glyphon::Color::rgb(r, g, b);
+        }
+        if let Some(rgb) = self.selected_bg {
+            config.bg_color_highlighted = Some(srgb_to_wgpu_color(rgb, 1.0));
+        }
+        if let Some([r, g, b]) = self.selected_fg {
+            config.font_color_highlighted = glyphon::Color::rgb(r, g, b);
+        }
+        if self.bottom {
+            config.position_bottom = true;
+        }
+        if self.monitor.is_some() {
+            config.monitor = self.monitor;
+        }
+    }
+}
 
 fn main() -> Result<(), i32> {
     env_logger::init();
-    app_main();
+
+    let args = match Args::parse(env::args()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            process::exit(1);
+        }
+    };
+
+    let mut config = Config::new();
+    args.apply_to_config(&mut config);
+
+    if args.watch {
+        watcher::watch_main(config);
+        return Ok(());
+    }
+
+    if args.toggle {
+        if daemon::send_toggle(&config).is_ok() {
+            return Ok(());
+        }
+        info!("No daemon is running, opening the launcher");
+    }
+
+    let mode = if args.dmenu {
+        Mode::Dmenu
+    } else {
+        Mode::Launcher
+    };
+    app_main(mode, config, args.daemon);
     Ok(())
 }
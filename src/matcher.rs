@@ -0,0 +1,338 @@
+// Scoring follows fzf: every matched character is worth the same, matches at the start of words,
+// camelCase humps and digits get a bonus, consecutive matches keep the bonus of the chunk they
+// start and gaps between matches cost a little.
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+
+const BONUS_BOUNDARY: i32 = SCORE_MATCH / 2;
+const BONUS_BOUNDARY_WHITE: i32 = BONUS_BOUNDARY + 2;
+const BONUS_BOUNDARY_DELIMITER: i32 = BONUS_BOUNDARY + 1;
+const BONUS_NON_WORD: i32 = SCORE_MATCH / 2;
+const BONUS_CAMEL_123: i32 = BONUS_BOUNDARY + SCORE_GAP_EXTENSION;
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+const NO_MATCH: i32 = i32::MIN / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    White,
+    NonWord,
+    Delimiter,
+    Lower,
+    Upper,
+    Letter,
+    Number,
+}
+
+impl CharClass {
+    fn of(c: char) -> CharClass {
+        if c.is_lowercase() {
+            CharClass::Lower
+        } else if c.is_uppercase() {
+            CharClass::Upper
+        } else if c.is_numeric() {
+            CharClass::Number
+        } else if c.is_alphabetic() {
+            CharClass::Letter
+        } else if c.is_whitespace() {
+            CharClass::White
+        } else if "/,:;|".contains(c) {
+            CharClass::Delimiter
+        } else {
+            CharClass::NonWord
+        }
+    }
+
+    fn is_word(&self) -> bool {
+        !matches!(
+            self,
+            CharClass::White | CharClass::NonWord | CharClass::Delimiter
+        )
+    }
+}
+
+/// Bonus for matching a character of class `class` that comes after one of class `prev_class`
+fn get_bonus(prev_class: CharClass, class: CharClass) -> i32 {
+    if class.is_word() {
+        match prev_class {
+            CharClass::White => return BONUS_BOUNDARY_WHITE,
+            CharClass::Delimiter => return BONUS_BOUNDARY_DELIMITER,
+            CharClass::NonWord => return BONUS_BOUNDARY,
+            _ => {}
+        }
+    }
+    if (prev_class == CharClass::Lower && class == CharClass::Upper)
+        || (prev_class != CharClass::Number && class == CharClass::Number)
+    {
+        return BONUS_CAMEL_123;
+    }
+    match class {
+        CharClass::White => BONUS_BOUNDARY_WHITE,
+        CharClass::NonWord | CharClass::Delimiter => BONUS_NON_WORD,
+        _ => 0,
+    }
+}
+
+/// Whether the case of the pattern has to match the case of the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMatching {
+    Sensitive,
+    Insensitive,
+    /// Case only matters when the pattern has uppercase characters
+    Smart,
+}
+
+/// Fuzzy subsequence matcher for a search entry
+pub struct Matcher {
+    pattern: Vec<char>,
+    case_sensitive: bool,
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, case_matching: CaseMatching) -> Matcher {
+        let case_sensitive = match case_matching {
+            CaseMatching::Sensitive => true,
+            CaseMatching::Insensitive => false,
+            CaseMatching::Smart => pattern.chars().any(|c| c.is_uppercase()),
+        };
+        let pattern = if case_sensitive {
+            pattern.chars().collect()
+        } else {
+            pattern.chars().flat_map(|c| c.to_lowercase()).collect()
+        };
+        Matcher {
+            pattern,
+            case_sensitive,
+        }
+    }
+
+    fn normalize(&self, c: char) -> char {
+        if self.case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    }
+
+    /// Whether `text` is exactly the pattern, with the same case sensitivity as `score`
+    pub fn is_exact(&self, text: &str) -> bool {
+        let mut chars = text.chars();
+        for p in &self.pattern {
+            match chars.next() {
+                Some(c) if self.normalize(c) == *p => {}
+                _ => return false,
+            }
+        }
+        chars.next().is_none()
+    }
+
+    /// Whether the pattern appears in `text` as it is, with the same case sensitivity as `score`
+    pub fn is_substring(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().map(|c| self.normalize(c)).collect();
+        text.windows(self.pattern.len().max(1))
+            .any(|window| window == self.pattern.as_slice())
+    }
+
+    /// Scores how well `text` matches the pattern, or `None` if the pattern is not a
+    /// subsequence of `text`. Higher is better.
+    pub fn score(&self, text: &str) -> Option<i32> {
+        if self.pattern.is_empty() {
+            return Some(0);
+        }
+
+        let original: Vec<char> = text.chars().collect();
+        let text: Vec<char> = original.iter().map(|c| self.normalize(*c)).collect();
+
+        // Cheap check before filling the score matrices
+        let mut pattern_chars = self.pattern.iter().peekable();
+        for c in &text {
+            if pattern_chars.peek() == Some(&c) {
+                pattern_chars.next();
+            }
+        }
+        if pattern_chars.peek().is_some() {
+            return None;
+        }
+
+        let mut prev_class = CharClass::White;
+        let bonuses: Vec<i32> = original
+            .iter()
+            .map(|c| {
+                let class = CharClass::of(*c);
+                let bonus = get_bonus(prev_class, class);
+                prev_class = class;
+                bonus
+            })
+            .collect();
+
+        let n = text.len();
+        // Best score with the current pattern char matched at `j`
+        let mut matched = vec![NO_MATCH; n];
+        // Best score with the current pattern char matched before `j`, paying for the gap
+        let mut gapped = vec![NO_MATCH; n];
+        // Length of the consecutive run ending at `j` for the best match at `j`
+        let mut consecutive = vec![0usize; n];
+
+        let mut prev_matched = vec![NO_MATCH; n];
+        let mut prev_gapped = vec![NO_MATCH; n];
+        let mut prev_consecutive = vec![0usize; n];
+
+        for (i, p) in self.pattern.iter().enumerate() {
+            for j in 0..n {
+                matched[j] = NO_MATCH;
+                consecutive[j] = 0;
+
+                if text[j] == *p {
+                    if i == 0 {
+                        matched[j] = SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER;
+                        consecutive[j] = 1;
+                    } else if j > 0 {
+                        if prev_gapped[j - 1] > NO_MATCH {
+                            matched[j] = prev_gapped[j - 1] + SCORE_MATCH + bonuses[j];
+                            consecutive[j] = 1;
+                        }
+                        if prev_matched[j - 1] > NO_MATCH {
+                            let run = prev_consecutive[j - 1] + 1;
+                            let bonus = bonuses[j].max(BONUS_CONSECUTIVE).max(bonuses[j + 1 - run]);
+                            let score = prev_matched[j - 1] + SCORE_MATCH + bonus;
+                            if score >= matched[j] {
+                                matched[j] = score;
+                                consecutive[j] = run;
+                            }
+                        }
+                    }
+                }
+
+                gapped[j] = NO_MATCH;
+                if j > 0 {
+                    if matched[j - 1] > NO_MATCH {
+                        gapped[j] = matched[j - 1] + SCORE_GAP_START;
+                    }
+                    if gapped[j - 1] > NO_MATCH {
+                        gapped[j] = gapped[j].max(gapped[j - 1] + SCORE_GAP_EXTENSION);
+                    }
+                }
+            }
+
+            std::mem::swap(&mut matched, &mut prev_matched);
+            std::mem::swap(&mut gapped, &mut prev_gapped);
+            std::mem::swap(&mut consecutive, &mut prev_consecutive);
+        }
+
+        // Characters after the last match are free
+        prev_matched
+            .iter()
+            .copied()
+            .filter(|score| *score > NO_MATCH)
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, text: &str) -> Option<i32> {
+        Matcher::new(pattern, CaseMatching::Smart).score(text)
+    }
+
+    /// The texts `pattern` matches, best first
+    fn rank<'a>(pattern: &str, texts: &[&'a str]) -> Vec<&'a str> {
+        let mut scored: Vec<(&str, i32)> = texts
+            .iter()
+            .filter_map(|text| score(pattern, text).map(|score| (*text, score)))
+            .collect();
+        scored.sort_by(|(_, a), (_, b)| b.cmp(a));
+        scored.into_iter().map(|(text, _)| text).collect()
+    }
+
+    #[test]
+    fn matches_subsequences() {
+        assert!(score("ffx", "Firefox").is_some());
+        assert!(score("fox", "Firefox").is_some());
+        assert!(score("xff", "Firefox").is_none());
+        assert!(score("firefoxes", "Firefox").is_none());
+        assert_eq!(score("", "Firefox"), Some(0));
+    }
+
+    #[test]
+    fn ranks_word_starts_first() {
+        assert_eq!(
+            rank(
+                "vsc",
+                &[
+                    "Revisit Scheduler",
+                    "Dev Tools Console",
+                    "Visual Studio Code"
+                ]
+            )[0],
+            "Visual Studio Code"
+        );
+    }
+
+    #[test]
+    fn camel_case_beats_gaps() {
+        assert!(score("fb", "FooBar") > score("fb", "Fabric"));
+    }
+
+    #[test]
+    fn word_start_beats_gaps() {
+        assert!(score("gc", "gnome-calculator") > score("gc", "gecko"));
+        assert!(score("sc", "Visual Studio Code") > score("sc", "Visual Basic"));
+    }
+
+    #[test]
+    fn consecutive_matches_keep_the_bonus_of_their_start() {
+        assert!(score("studio", "Visual Studio Code") > score("studio", "Sxtxuxdxixo"));
+        assert!(score("code", "Visual Studio Code") > score("code", "Encoder"));
+    }
+
+    #[test]
+    fn exact_matches_are_told_apart() {
+        let matcher = Matcher::new("firefox", CaseMatching::Smart);
+        assert!(matcher.is_exact("Firefox"));
+        assert!(!matcher.is_exact("Firefox Developer Edition"));
+        assert!(!matcher.is_exact("Fire"));
+        assert!(matcher.is_substring("Firefox Developer Edition"));
+        assert!(!matcher.is_substring("Fire"));
+    }
+
+    #[test]
+    fn follows_case_matching() {
+        assert!(score("fire", "Firefox").is_some());
+        assert!(score("Fire", "firefox").is_none());
+        assert!(Matcher::new("fire", CaseMatching::Sensitive)
+            .score("Firefox")
+            .is_none());
+        assert!(Matcher::new("FIRE", CaseMatching::Insensitive)
+            .score("firefox")
+            .is_some());
+    }
+
+    #[test]
+    fn classifies_bonuses() {
+        assert_eq!(
+            get_bonus(CharClass::White, CharClass::Lower),
+            BONUS_BOUNDARY_WHITE
+        );
+        assert_eq!(
+            get_bonus(CharClass::Delimiter, CharClass::Lower),
+            BONUS_BOUNDARY_DELIMITER
+        );
+        assert_eq!(
+            get_bonus(CharClass::NonWord, CharClass::Upper),
+            BONUS_BOUNDARY
+        );
+        assert_eq!(
+            get_bonus(CharClass::Lower, CharClass::Upper),
+            BONUS_CAMEL_123
+        );
+        assert_eq!(
+            get_bonus(CharClass::Lower, CharClass::Number),
+            BONUS_CAMEL_123
+        );
+        assert_eq!(get_bonus(CharClass::Lower, CharClass::Lower), 0);
+    }
+}
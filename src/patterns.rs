@@ -0,0 +1,43 @@
+static REGEX_PREFIX: &str = "re:";
+
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+
+/// A glob like `*-config` that has to match a whole name, or a regex after `re:` that matches
+/// anywhere in a name unless it is anchored
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Pattern, String> {
+        match pattern.strip_prefix(REGEX_PREFIX) {
+            Some(regex) => Regex::new(regex)
+                .map(Pattern::Regex)
+                .map_err(|err| err.to_string()),
+            None => Glob::new(pattern)
+                .map(|glob| Pattern::Glob(glob.compile_matcher()))
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob.is_match(name),
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Whether an entry known by `names` is kept: none of the `ignored` patterns may match any of
+/// them, and unless `include_only` is empty one of its patterns has to
+pub fn is_included(ignored: &[Pattern], include_only: &[Pattern], names: &[&str]) -> bool {
+    let matches_any = |patterns: &[Pattern]| {
+        patterns
+            .iter()
+            .any(|pattern| names.iter().any(|name| pattern.is_match(name)))
+    };
+    !matches_any(ignored) && (include_only.is_empty() || matches_any(include_only))
+}
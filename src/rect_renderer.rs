@@ -0,0 +1,153 @@
+static SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+/// Two floats for the position and four for the color
+const VERTEX_SIZE: u64 = 6 * 4;
+const VERTICES_PER_RECT: u64 = 6;
+
+/// Solid colored rectangle in physical pixels
+pub struct Rect {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: wgpu::Color,
+}
+
+/// Draws solid colored rectangles, like the background of the highlighted entry
+pub struct RectRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl RectRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("rect shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("rect pipeline layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("rect pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: VERTEX_SIZE,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer: Self::create_vertex_buffer(device, 1),
+            vertex_count: 0,
+        }
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, rect_capacity: u64) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("rect vertex buffer"),
+            size: rect_capacity * VERTICES_PER_RECT * VERTEX_SIZE,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Uploads the rectangles to draw in the next `render` call
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resolution: (u32, u32),
+        rects: &[Rect],
+    ) {
+        let width = resolution.0 as f32;
+        let height = resolution.1 as f32;
+
+        let mut vertices: Vec<u8> =
+            Vec::with_capacity(rects.len() * (VERTICES_PER_RECT * VERTEX_SIZE) as usize);
+        for rect in rects {
+            // Pixel coordinates to normalized device coordinates
+            let left = rect.left / width * 2.0 - 1.0;
+            let right = (rect.left + rect.width) / width * 2.0 - 1.0;
+            let top = 1.0 - rect.top / height * 2.0;
+            let bottom = 1.0 - (rect.top + rect.height) / height * 2.0;
+            let color = [
+                rect.color.r as f32,
+                rect.color.g as f32,
+                rect.color.b as f32,
+                rect.color.a as f32,
+            ];
+
+            for (x, y) in [
+                (left, top),
+                (left, bottom),
+                (right, bottom),
+                (left, top),
+                (right, bottom),
+                (right, top),
+            ] {
+                for value in [x, y].iter().chain(color.iter()) {
+                    vertices.extend_from_slice(&value.to_ne_bytes());
+                }
+            }
+        }
+
+        if vertices.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = Self::create_vertex_buffer(device, rects.len() as u64);
+        }
+        queue.write_buffer(&self.vertex_buffer, 0, &vertices);
+        self.vertex_count = (rects.len() as u64 * VERTICES_PER_RECT) as u32;
+    }
+
+    pub fn render<'pass>(&'pass self, pass: &mut wgpu::RenderPass<'pass>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+}
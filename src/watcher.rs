@@ -0,0 +1,226 @@
+/// Package managers change many files at once, so the cache is updated once they are quiet for
+/// this long
+const SETTLE_TIME_MS: u16 = 500;
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::OsString,
+    os::fd::AsFd,
+    path::Path,
+    process,
+};
+
+use log::{error, info};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags, PollTimeout},
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor},
+};
+
+use crate::config::{normalize_dir, Config};
+use crate::executables;
+
+/// Directories that hold executables, even when they don't exist at the moment
+fn get_root_dirs(config: &Config) -> Vec<String> {
+    let path_dirs = env::var("PATH").unwrap_or_default();
+    path_dirs
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(normalize_dir)
+        .chain(config.extra_directories.iter().cloned())
+        .chain(
+            executables::get_data_dirs()
+                .into_iter()
+                .map(|dir| dir + "/applications"),
+        )
+        .collect()
+}
+
+/// A watched directory
+struct WatchedDir {
+    path: String,
+    /// Names of the directories in it that hold executables, when it is only watched as their
+    /// parent. Changes to its other entries, like GTK's `recently-used.xbel` in
+    /// `~/.local/share`, are ignored.
+    root_dir_names: Option<HashSet<OsString>>,
+}
+
+/// Keeps the inotify watches on the scanned directories, and on the parents of the directories
+/// that hold executables so it notices when those are deleted and created again
+struct Watches {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, WatchedDir>,
+}
+
+impl Watches {
+    fn update(&mut self, config: &Config, paths: &[String]) {
+        let scanned_dirs = executables::get_scanned_dirs(config, paths);
+        let mut root_dir_names_by_parent: HashMap<String, HashSet<OsString>> = HashMap::new();
+        for root_dir in get_root_dirs(config) {
+            let root_dir = Path::new(&root_dir);
+            if let (Some(parent), Some(name)) = (root_dir.parent(), root_dir.file_name()) {
+                root_dir_names_by_parent
+                    .entry(parent.display().to_string())
+                    .or_default()
+                    .insert(name.to_os_string());
+            }
+        }
+
+        // Watching a directory again replaces its flags, so a directory that is both scanned and
+        // a parent gets them combined
+        let mut flags_by_dir: HashMap<String, AddWatchFlags> = HashMap::new();
+        for (dir, flags) in scanned_dirs
+            .iter()
+            .map(|dir| (dir, get_scanned_dir_flags()))
+            .chain(
+                root_dir_names_by_parent
+                    .keys()
+                    .map(|dir| (dir, get_parent_dir_flags())),
+            )
+        {
+            *flags_by_dir
+                .entry(dir.clone())
+                .or_insert(AddWatchFlags::empty()) |= flags;
+        }
+
+        let mut dirs = HashMap::new();
+        for (dir, flags) in flags_by_dir {
+            match self.inotify.add_watch(dir.as_str(), flags) {
+                Ok(watch) => {
+                    // Every change in a scanned directory matters
+                    let root_dir_names = if scanned_dirs.contains(&dir) {
+                        None
+                    } else {
+                        root_dir_names_by_parent.remove(&dir)
+                    };
+                    dirs.insert(
+                        watch,
+                        WatchedDir {
+                            path: dir,
+                            root_dir_names,
+                        },
+                    );
+                }
+                // Directories that don't exist are watched through their parents instead
+                Err(Errno::ENOENT) => {}
+                Err(err) => error!("Failed to watch '{dir}': {:?}", err),
+            }
+        }
+
+        for watch in self.dirs.keys() {
+            if !dirs.contains_key(watch) {
+                // The watch is already gone when its directory was deleted
+                let _ = self.inotify.rm_watch(*watch);
+            }
+        }
+        info!("Watching {} directories", dirs.len());
+        self.dirs = dirs;
+    }
+
+    /// Waits for events and returns the directories they happened in. With a timeout, returns
+    /// nothing if there were no events by then.
+    fn wait(&self, timeout: PollTimeout) -> HashSet<String> {
+        let mut fds = [PollFd::new(self.inotify.as_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, timeout) {
+            Ok(0) => return HashSet::new(),
+            Ok(_) => {}
+            Err(Errno::EINTR) => return HashSet::new(),
+            Err(err) => {
+                error!("Failed to wait for inotify events: {:?}", err);
+                process::exit(1);
+            }
+        }
+
+        let mut changed_dirs = HashSet::new();
+        loop {
+            match self.inotify.read_events() {
+                Ok(events) => {
+                    for event in events {
+                        let Some(dir) = self.dirs.get(&event.wd) else {
+                            continue;
+                        };
+                        if let Some(root_dir_names) = &dir.root_dir_names {
+                            if !event
+                                .name
+                                .as_ref()
+                                .is_some_and(|name| root_dir_names.contains(name))
+                            {
+                                continue;
+                            }
+                        }
+                        info!("{:?} {:?} in '{}'", event.mask, event.name, dir.path);
+                        changed_dirs.insert(dir.path.clone());
+                    }
+                }
+                Err(Errno::EAGAIN) => break,
+                Err(err) => {
+                    error!("Failed to read inotify events: {:?}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        changed_dirs
+    }
+}
+
+fn get_scanned_dir_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_ATTRIB
+        | AddWatchFlags::IN_DELETE_SELF
+        | AddWatchFlags::IN_MOVE_SELF
+        | AddWatchFlags::IN_ONLYDIR
+}
+
+fn get_parent_dir_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_ONLYDIR
+}
+
+/// Keeps the cache up-to-date as executables are installed and removed, so the launcher never
+/// has to rescan
+pub fn watch_main(config: Config) {
+    let Ok(_lock) = executables::lock_watcher(&config) else {
+        error!("Another watcher is already running");
+        process::exit(1);
+    };
+
+    let inotify = match Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC) {
+        Ok(inotify) => inotify,
+        Err(err) => {
+            error!("Failed to initialize inotify: {:?}", err);
+            process::exit(1);
+        }
+    };
+    let mut watches = Watches {
+        inotify,
+        dirs: HashMap::new(),
+    };
+
+    let mut changed_dirs = HashSet::new();
+    loop {
+        // PATH directories that didn't exist before may exist now
+        let paths = executables::get_binary_dirs(&config);
+        // Watch before scanning so nothing that changes during the scan is missed
+        watches.update(&config, &paths);
+        if let Some(executables) = executables::update_cache(&config, &paths, &changed_dirs) {
+            info!("Updated cache with {} executables", executables.len());
+        }
+
+        changed_dirs = watches.wait(PollTimeout::NONE);
+        loop {
+            let more_changed_dirs = watches.wait(PollTimeout::from(SETTLE_TIME_MS));
+            if more_changed_dirs.is_empty() {
+                break;
+            }
+            changed_dirs.extend(more_changed_dirs);
+        }
+    }
+}